@@ -0,0 +1,223 @@
+//! `#[derive(Dialog)]` for the config sections of bender_config. The derived
+//! `ask()` prompts for every field, `compare()` shows every field next to the
+//! value of the other config and lets the user pick one. Defaults come from
+//! the `default` attribute of a field or the `Default` implementation of the
+//! section. `descriptions()` returns the texts and prompts, the JSON schema
+//! uses them as descriptions.
+//!
+//! ```ignore
+//! #[derive(Dialog)]
+//! #[dialog(section = "bender-worker", text = "Shown below the section label")]
+//! pub struct Worker{
+//!     #[dialog(skip)]
+//!     pub id: Uuid,
+//!     #[dialog(prompt = "How many frames should the worker render at once?",
+//!              block = "\n How many frames should a worker accept at once? ")]
+//!     pub workload: usize
+//! }
+//! ```
+//!
+//! Section attributes:
+//! - `section`: the label of the section (defaults to the struct name)
+//! - `key`: the key of the section in the config.toml (defaults to the
+//!   lowercase struct name)
+//! - `text`: a explanation printed below the label by `ask()`
+//!
+//! Field attributes:
+//! - `prompt`: the question `ask()` asks (required unless `skip`)
+//! - `block`: the label `compare()` prints (defaults to the prompt)
+//! - `text`: a explanation printed before the prompt by `ask()`. For skipped
+//!   fields it is only used as description
+//! - `default`: the default `ask()` offers (or gives skipped fields), parsed
+//!   with `FromStr`. Without it the value from `Default` is used
+//! - `skip`: the field isn't asked, `ask()` gives it its default value and
+//!   `compare()` keeps the current one
+//! - `with`: a module with the functions
+//!   `ask(prompter: &mut dyn Prompter) -> T` and
+//!   `compare(prompter: &mut dyn Prompter, this: &T, other: Option<&T>) -> T`
+//!   that are used for this field instead
+//!
+//! The generated code refers to the `wizard` module by its absolute path, so
+//! the derive only works within bender_config itself.
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+
+#[proc_macro_derive(Dialog, attributes(dialog))]
+pub fn derive_dialog(input: TokenStream) -> TokenStream{
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input){
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into()
+    }
+}
+
+
+/// The `#[dialog(...)]` attributes of a struct or field
+#[derive(Default)]
+struct Attributes{
+    section: Option<String>,
+    key: Option<String>,
+    text: Option<String>,
+    prompt: Option<String>,
+    block: Option<String>,
+    with: Option<String>,
+    default: Option<String>,
+    skip: bool
+}
+
+
+/// Collect all `#[dialog(...)]` attributes
+fn parse_attributes(attrs: &[syn::Attribute]) -> syn::Result<Attributes>{
+    let mut parsed = Attributes::default();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("dialog")){
+        let list = match attr.parse_meta()?{
+            Meta::List(list) => list,
+            other => return Err(syn::Error::new_spanned(other, "expected #[dialog(...)]"))
+        };
+        for nested in list.nested{
+            match nested{
+                NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("skip") => parsed.skip = true,
+                NestedMeta::Meta(Meta::NameValue(ref nv)) => {
+                    let value = match nv.lit{
+                        Lit::Str(ref s) => s.value(),
+                        ref other => return Err(syn::Error::new_spanned(other, "expected a string"))
+                    };
+                    let name = nv.path.get_ident().map(|i| i.to_string()).unwrap_or_default();
+                    let slot = match name.as_str(){
+                        "section" => &mut parsed.section,
+                        "key"     => &mut parsed.key,
+                        "text"    => &mut parsed.text,
+                        "prompt"  => &mut parsed.prompt,
+                        "block"   => &mut parsed.block,
+                        "with"    => &mut parsed.with,
+                        "default" => &mut parsed.default,
+                        _ => return Err(syn::Error::new_spanned(&nv.path, "unknown dialog attribute"))
+                    };
+                    *slot = Some(value);
+                },
+                other => return Err(syn::Error::new_spanned(other, "unknown dialog attribute"))
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2>{
+    let name = &input.ident;
+    let fields = match input.data{
+        Data::Struct(ref data) => match data.fields{
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(input, "Dialog can only be derived for structs with named fields"))
+        },
+        _ => return Err(syn::Error::new_spanned(input, "Dialog can only be derived for structs"))
+    };
+
+    let section = parse_attributes(&input.attrs)?;
+    let label = section.section.unwrap_or_else(|| name.to_string());
+    let key = section.key.unwrap_or_else(|| name.to_string().to_lowercase());
+    let intro = section.text.as_ref().map(|t| quote!{ prompter.print_text(#t); });
+
+    let mut descriptions = Vec::new();
+    if let Some(ref text) = section.text{
+        descriptions.push(quote!{ ("", #text) });
+    }
+    let mut asks = Vec::new();
+    let mut compares = Vec::new();
+    let mut names = Vec::new();
+    for field in fields{
+        let ident = field.ident.as_ref().expect("named field");
+        let attrs = parse_attributes(&field.attrs)?;
+        names.push(ident.clone());
+        if let Some(description) = attrs.prompt.as_ref().or(attrs.text.as_ref()){
+            let field_name = ident.to_string();
+            descriptions.push(quote!{ (#field_name, #description) });
+        }
+        let dotted = format!("{}.{}", key, ident);
+        let default = match attrs.default{
+            Some(ref d) => {
+                let ty = &field.ty;
+                quote!{
+                    match <#ty as ::std::str::FromStr>::from_str(#d){
+                        Ok(value) => value,
+                        Err(err) => panic!("Invalid #[dialog(default = {:?})] for {}: {}", #d, #dotted, err)
+                    }
+                }
+            },
+            None => quote!{ defaults.#ident }
+        };
+        if attrs.skip{
+            asks.push(quote!{ let #ident = #default; });
+            compares.push(quote!{ let #ident = ::std::clone::Clone::clone(&self.#ident); });
+            continue;
+        }
+        if let Some(with) = attrs.with{
+            if attrs.default.is_some(){
+                return Err(syn::Error::new_spanned(field, "#[dialog(default = \"...\")] has no effect together with #[dialog(with = \"...\")]"));
+            }
+            let module: syn::Path = syn::parse_str(with.as_str())?;
+            let text = attrs.text.map(|t| quote!{ prompter.print_text(#t); });
+            let block = attrs.block.map(|b| quote!{ prompter.print_block(#b); });
+            asks.push(quote!{
+                #text
+                let #ident = #module::ask(prompter);
+            });
+            compares.push(quote!{
+                #block
+                let #ident = #module::compare(prompter, &self.#ident, other.map(|o| &o.#ident));
+            });
+            continue;
+        }
+        let prompt = match attrs.prompt{
+            Some(p) => p,
+            None => return Err(syn::Error::new_spanned(field, "missing #[dialog(prompt = \"...\")], #[dialog(with = \"...\")] or #[dialog(skip)]"))
+        };
+        let block = attrs.block.unwrap_or_else(|| prompt.clone());
+        let text = attrs.text.map(|t| quote!{ prompter.print_text(#t); });
+        asks.push(quote!{
+            #text
+            let #ident = ::wizard::input(prompter, #dotted, #prompt, #default);
+        });
+        compares.push(quote!{
+            prompter.print_block(#block);
+            let #ident = ::wizard::differ(prompter,
+                                          ::std::clone::Clone::clone(&self.#ident),
+                                          other.map(|o| ::std::clone::Clone::clone(&o.#ident)));
+        });
+    }
+
+    // The parentheses around the trait are needed, as in the 2015 edition
+    // `dyn ::wizard::Prompter` would be read as the path `dyn::wizard::Prompter`
+    Ok(quote!{
+        impl ::wizard::Dialog for #name{
+            fn ask(prompter: &mut dyn (::wizard::Prompter)) -> Self{
+                #[allow(unused_variables)]
+                let defaults = <Self as ::std::default::Default>::default();
+                prompter.print_text("");
+                prompter.print_sectionlabel(#label);
+                #intro
+                #(#asks)*
+                Self{ #(#names),* }
+            }
+
+            fn compare(&self, prompter: &mut dyn (::wizard::Prompter), other: ::std::option::Option<&Self>) -> Self{
+                prompter.print_text("");
+                prompter.print_sectionlabel(#label);
+                #(#compares)*
+                Self{ #(#names),* }
+            }
+
+            fn descriptions() -> ::std::vec::Vec<(&'static str, &'static str)>{
+                vec![#(#descriptions),*]
+            }
+        }
+    })
+}
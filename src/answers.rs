@@ -0,0 +1,281 @@
+//! Unattended runs of the wizard, e.g. for provisioning with Ansible or in CI.
+//! Instead of asking on the terminal, the `AnswersPrompter` answers every
+//! prompt of `Config::ask()` from a answers file (TOML or JSON, using the same
+//! structure as the config.toml) or from `BENDER_*` environment variables.
+//! Prompts without answer use the default an interactive run would offer. Every prompt is
+//! recorded together with the answer and where it came from:
+//! ```ignore
+//! let answers = Answers::from_file("answers.toml")?.with_env();
+//! let (config, log) = Config::ask_unattended(answers)?;
+//! for prompt in log{
+//!     println!("{}", prompt);
+//! }
+//! ```
+use ::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt;
+use toml::Value;
+use wizard::{Dialog, Prompter};
+
+
+/// Where the answer to a prompt came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnswerSource{
+    /// A answers file at the given path
+    File(String),
+    Environment,
+    /// Set via `Answers::set()`
+    Manual,
+    /// No answer was given, the default of the prompt was used
+    Default
+}
+
+impl fmt::Display for AnswerSource{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            AnswerSource::File(path)  => write!(f, "{}", path),
+            AnswerSource::Environment => write!(f, "environment"),
+            AnswerSource::Manual      => write!(f, "manual"),
+            AnswerSource::Default     => write!(f, "default")
+        }
+    }
+}
+
+
+/// A prompt of the wizard and the answer it got during a unattended run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt{
+    /// The dotted key of the answered value, e.g. `worker.workload`
+    pub key: String,
+    pub prompt: String,
+    pub answer: String,
+    pub source: AnswerSource
+}
+
+impl fmt::Display for Prompt{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}: {} [{}]", self.prompt, self.answer, self.source)
+    }
+}
+
+
+/// Answers for the prompts of the wizard, by dotted key
+#[derive(Debug, Clone, Default)]
+pub struct Answers{
+    values: BTreeMap<String, (String, AnswerSource)>
+}
+
+
+impl Answers{
+    /// No answers at all, every prompt uses its default
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    /// Read the answers from a TOML file, or a JSON file if the path ends
+    /// with `.json`
+    pub fn from_file<S>(path: S) -> ConfigResult<Self> where S: Into<String>{
+        let path = path.into();
+        let contents = fs::read_to_string(path.as_str()).map_err(|err| ConfigError::from_io(path.as_str(), err))?;
+        let root: Value = if path.ends_with(".json"){
+            serde_json::from_str(contents.as_str())
+                .map_err(|err| ConfigError::Decode(path.clone(), err.to_string()))?
+        }else{
+            toml::from_str(contents.as_str()).map_err(|err| ConfigError::Parse(path.clone(), err))?
+        };
+        let mut answers = Self::new();
+        let mut flat = Vec::new();
+        flatten(&root, "", &mut flat);
+        for (key, value) in flat{
+            answers.values.insert(key, (value, AnswerSource::File(path.clone())));
+        }
+        Ok(answers)
+    }
+
+    /// Add the answers from `BENDER_*` environment variables (see the
+    /// `overrides` module for the naming), they take precedence
+    pub fn with_env(self) -> Self{
+        self.with_env_from(env::vars())
+    }
+
+    /// Like `with_env()` but reads from the given variables
+    pub fn with_env_from<I>(mut self, vars: I) -> Self where I: IntoIterator<Item=(String, String)>{
+        let root = Value::try_from(Config::default()).expect("a Config always serializes");
+        for (key, value) in overrides::collect(&root, vars){
+            self.values.insert(key, (value, AnswerSource::Environment));
+        }
+        self
+    }
+
+    /// Answer the prompt for `key` with `value`
+    pub fn set<K, V>(mut self, key: K, value: V) -> Self where K: Into<String>, V: Into<String>{
+        self.values.insert(key.into(), (value.into(), AnswerSource::Manual));
+        self
+    }
+}
+
+
+/// A Prompter that answers from `Answers` instead of asking. Prompts without
+/// answer get their default, selections and confirmations always take the
+/// default. Nothing is printed, every prompt is recorded in the log instead
+#[derive(Debug)]
+pub struct AnswersPrompter{
+    answers: Answers,
+    used: BTreeSet<String>,
+    log: Vec<Prompt>,
+    errors: Vec<ConfigError>
+}
+
+
+impl AnswersPrompter{
+    pub fn new(answers: Answers) -> Self{
+        Self{ answers, used: BTreeSet::new(), log: Vec::new(), errors: Vec::new() }
+    }
+
+    /// Return the log of all prompts, or the first invalid answer. Answers
+    /// that didn't belong to any prompt are returned as well
+    pub fn finish(self) -> ConfigResult<(Vec<Prompt>, Answers)>{
+        if let Some(err) = self.errors.into_iter().next(){
+            return Err(err);
+        }
+        let used = self.used;
+        let values = self.answers.values.into_iter()
+                         .filter(|(key, _)| !used.contains(key))
+                         .collect();
+        Ok((self.log, Answers{ values }))
+    }
+}
+
+
+impl Prompter for AnswersPrompter{
+    fn input(&mut self, key: &str, prompt: &str, default: &str, validate: &dyn Fn(&str) -> Result<(), String>) -> String{
+        let (answer, source) = match self.answers.values.get(key){
+            Some((raw, source)) => {
+                self.used.insert(key.to_string());
+                match validate(raw.as_str()){
+                    Ok(_) => (raw.clone(), source.clone()),
+                    Err(err) => {
+                        self.errors.push(ConfigError::InvalidValue(key.to_string(), raw.clone(), err));
+                        (default.to_string(), AnswerSource::Default)
+                    }
+                }
+            },
+            None => (default.to_string(), AnswerSource::Default)
+        };
+        self.log.push(Prompt{
+            key: key.to_string(),
+            prompt: prompt.to_string(),
+            answer: answer.clone(),
+            source
+        });
+        answer
+    }
+
+    fn select(&mut self, _prompt: &str, _items: &[String], default: usize) -> usize{
+        default
+    }
+
+    fn confirm(&mut self, _prompt: &str, default: bool) -> bool{
+        default
+    }
+
+    fn print_sectionlabel(&mut self, _message: &str){}
+
+    fn print_block(&mut self, _message: &str){}
+
+    fn print_text(&mut self, _message: &str){}
+}
+
+
+/// Turn a (nested) toml Value into `(dotted key, value)` pairs
+fn flatten(value: &Value, prefix: &str, out: &mut Vec<(String, String)>){
+    match value{
+        Value::Table(table) => {
+            for (k, v) in table{
+                let key = if prefix.is_empty(){ k.clone() }else{ format!("{}.{}", prefix, k) };
+                flatten(v, key.as_str(), out);
+            }
+        },
+        Value::String(s) => out.push((prefix.to_string(), s.clone())),
+        other => out.push((prefix.to_string(), other.to_string()))
+    }
+}
+
+
+impl Config{
+    /// Run the wizard without a terminal, answering every prompt from
+    /// `answers` or with its default. Answers for values the wizard doesn't
+    /// ask for (e.g. `backup_count`) are applied afterwards. Returns the
+    /// Config and the log of all prompts
+    pub fn ask_unattended(answers: Answers) -> ConfigResult<(Config, Vec<Prompt>)>{
+        let mut prompter = AnswersPrompter::new(answers);
+        let config = Config::ask(&mut prompter);
+        let (mut log, rest) = prompter.finish()?;
+        let mut config = overrides::apply(&config, rest.values.iter().map(|(k, (v, _))| (k.as_str(), v.as_str())))?;
+        for (key, (answer, source)) in rest.values{
+            log.push(Prompt{ prompt: key.clone(), key, answer, source });
+        }
+        config.version = CURRENT_VERSION;
+        Ok((config, log))
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use answers::*;
+
+    #[test]
+    fn defaults_and_answers() {
+        let answers = Answers::new().set("worker.workload", "3")
+                                    .set("backup_count", "7")
+                                    .with_env_from(vec![("BENDER_SERVERNAME".to_string(), "farm".to_string()),
+                                                        ("BENDER_HOME".to_string(), "/opt/bender".to_string())]);
+        let (config, log) = Config::ask_unattended(answers).unwrap();
+        assert_eq!(config.worker.workload, 3);
+        assert_eq!(config.backup_count, 7);
+        assert_eq!(config.servername, "farm");
+        assert_eq!(config.rabbitmq.url, "amqp://localhost//");
+
+        let workload = log.iter().find(|p| p.key == "worker.workload").unwrap();
+        assert_eq!(workload.answer, "3");
+        assert_eq!(workload.source, AnswerSource::Manual);
+        let url = log.iter().find(|p| p.key == "rabbitmq.url").unwrap();
+        assert_eq!(url.prompt, "RabbitMQ URL");
+        assert_eq!(url.source, AnswerSource::Default);
+    }
+
+    #[test]
+    fn invalid_answers() {
+        match Config::ask_unattended(Answers::new().set("worker.workload", "many")){
+            Err(ConfigError::InvalidValue(key, _, _)) => assert_eq!(key, "worker.workload"),
+            other => panic!("Expected a invalid value, got {:?}", other.map(|(c, _)| c))
+        }
+        match Config::ask_unattended(Answers::new().set("worker.wrokload", "2")){
+            Err(ConfigError::UnknownKey(key)) => assert_eq!(key, "worker.wrokload"),
+            other => panic!("Expected a unknown key, got {:?}", other.map(|(c, _)| c))
+        }
+    }
+
+    #[test]
+    fn answers_file() {
+        let mut p = std::env::temp_dir();
+        p.push("bender_config_answers.json");
+        let p = p.to_string_lossy().to_string();
+        fs::write(p.as_str(), r#"{"paths": {"upload": "/srv/bender"}, "janitor": {"checking_period": "30s"}}"#).unwrap();
+        let (config, _) = Config::ask_unattended(Answers::from_file(p.as_str()).unwrap()).unwrap();
+        assert_eq!(config.paths.upload, "/srv/bender");
+        assert_eq!(config.janitor.checking_period, HumanDuration::from_secs(30));
+        fs::write(p.as_str(), "{\"paths\": ").unwrap();
+        match Answers::from_file(p.as_str()){
+            Err(ConfigError::Decode(origin, _)) => assert_eq!(origin, p),
+            other => panic!("Expected a Decode error, got {:?}", other)
+        }
+        fs::remove_file(p).unwrap();
+    }
+}
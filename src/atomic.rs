@@ -0,0 +1,190 @@
+//! Crash-safe file writes. The contents are written to a temporary file in
+//! the same directory, flushed to disk and then renamed over the target. A
+//! reader (or a crash at any point) therefore only ever sees the complete old
+//! or the complete new file, never a truncated one. This means the directory
+//! of the target has to be writable, not just the target itself. If the
+//! target already exists, its mode and ownership are carried over to the new
+//! file, unless it is written with `write_atomic_strict()`. Carrying over the
+//! owner needs the right to chown, writers without it (e.g. members of the
+//! group of a root owned file) end up owning the new file.
+use ::*;
+use std::fs::OpenOptions;
+
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+
+
+/// Atomically replace the file at `path` with `contents`
+pub fn write_atomic<S>(path: S, contents: &[u8]) -> ConfigResult<()> where S: Into<String>{
+    write_atomic_with_mode(path, contents, None)
+}
+
+
+/// Like `write_atomic()`, but new files are created with the given mode
+/// (Unix only). Existing files keep their mode
+pub fn write_atomic_with_mode<S>(path: S, contents: &[u8], mode: Option<u32>) -> ConfigResult<()> where S: Into<String>{
+    write(path.into(), contents, Ownership::Inherit(mode))
+}
+
+
+/// Like `write_atomic()`, but the file always gets the given mode and (if
+/// given) uid and gid, whatever the existing file had (Unix only). Changing
+/// the owner usually requires root
+pub fn write_atomic_strict<S>(path: S, contents: &[u8], mode: u32, uid: Option<u32>, gid: Option<u32>) -> ConfigResult<()> where S: Into<String>{
+    write(path.into(), contents, Ownership::Strict(mode, uid, gid))
+}
+
+
+/// How mode and owner of the written file are chosen
+enum Ownership{
+    /// Those of the existing file. New files get the mode, if any
+    Inherit(Option<u32>),
+    /// Always this mode, uid and gid
+    Strict(u32, Option<u32>, Option<u32>)
+}
+
+
+fn write(path: String, contents: &[u8], ownership: Ownership) -> ConfigResult<()>{
+    let target = PathBuf::from(path.as_str());
+    let tmp = temporary_path(&target);
+
+    let result = write_tmp(&target, &tmp, contents, &ownership)
+                     .and_then(|_| fs::rename(&tmp, &target))
+                     .map_err(|err| ConfigError::from_io(path.as_str(), err));
+    if result.is_err(){
+        let _ = fs::remove_file(&tmp);
+    }
+    result?;
+    sync_dir(&target).map_err(|err| ConfigError::from_io(path.as_str(), err))
+}
+
+
+/// Return a path for the temporary file next to `target`
+pub(crate) fn temporary_path(target: &std::path::Path) -> PathBuf{
+    let name = target.file_name()
+                     .map(|n| n.to_string_lossy().to_string())
+                     .unwrap_or_else(|| "bender".to_string());
+    let suffix: String = thread_rng().sample_iter(&Alphanumeric).take(8).collect();
+    target.with_file_name(format!(".{}.{}.tmp", name, suffix))
+}
+
+
+/// Write and flush the temporary file, with the mode and owner of the target
+/// or the strict ones
+// Option::is_some_and needs Rust 1.70
+#[allow(unknown_lints, clippy::unnecessary_map_or)]
+fn write_tmp(target: &std::path::Path, tmp: &std::path::Path, contents: &[u8], ownership: &Ownership) -> std::io::Result<()>{
+    let existing = fs::metadata(target).ok();
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    // (mode, uid, gid) of the new file
+    #[cfg(unix)]
+    let (mode, uid, gid) = match (ownership, &existing){
+        (Ownership::Strict(mode, uid, gid), _) => (Some(*mode), *uid, *gid),
+        (Ownership::Inherit(_), Some(m))       => (Some(m.permissions().mode() & 0o7777), Some(m.uid()), Some(m.gid())),
+        (Ownership::Inherit(mode), None)       => (*mode, None, None)
+    };
+    #[cfg(unix)]
+    {
+        if let Some(mode) = mode{
+            options.mode(mode);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = ownership;
+
+    let mut file = options.open(tmp)?;
+    file.write_all(contents)?;
+
+    #[cfg(unix)]
+    {
+        // The mode passed to open is masked by the umask, set it explicitly
+        if existing.is_some() || matches!(ownership, Ownership::Strict(_, _, _)){
+            if let Some(mode) = mode{
+                fs::set_permissions(tmp, fs::Permissions::from_mode(mode))?;
+            }
+        }
+        let created = file.metadata()?;
+        if uid.map_or(false, |u| u != created.uid()) || gid.map_or(false, |g| g != created.gid()){
+            match std::os::unix::fs::chown(tmp, uid, gid){
+                Ok(_) => (),
+                // Keeping the owner is best effort, unlike a strict owner
+                Err(ref err) if err.kind() == std::io::ErrorKind::PermissionDenied
+                                && matches!(ownership, Ownership::Inherit(_)) => {
+                    // The group might still be possible
+                    let _ = std::os::unix::fs::chown(tmp, None, gid);
+                },
+                Err(err) => return Err(err)
+            }
+        }
+    }
+
+    file.sync_all()
+}
+
+
+/// Flush the directory entry of `target` to disk, so the rename survives a crash
+#[cfg(unix)]
+fn sync_dir(target: &std::path::Path) -> std::io::Result<()>{
+    let mut dir = target.to_path_buf();
+    dir.pop();
+    if dir.as_os_str().is_empty(){
+        dir.push(".");
+    }
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_target: &std::path::Path) -> std::io::Result<()>{
+    Ok(())
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use atomic::*;
+
+    #[test]
+    fn replaces_and_keeps_mode() {
+        let mut p = std::env::temp_dir();
+        p.push("bender_config_atomic.toml");
+        let p = p.to_string_lossy().to_string();
+        fs::write(p.as_str(), "old").unwrap();
+        #[cfg(unix)]
+        fs::set_permissions(p.as_str(), fs::Permissions::from_mode(0o640)).unwrap();
+
+        write_atomic(p.as_str(), b"new").unwrap();
+        assert_eq!(fs::read_to_string(p.as_str()).unwrap(), "new");
+        #[cfg(unix)]
+        assert_eq!(fs::metadata(p.as_str()).unwrap().permissions().mode() & 0o777, 0o640);
+        fs::remove_file(p).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn strict_mode() {
+        let mut p = std::env::temp_dir();
+        p.push("bender_config_atomic.secret");
+        let p = p.to_string_lossy().to_string();
+        fs::write(p.as_str(), "old").unwrap();
+        fs::set_permissions(p.as_str(), fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_atomic_strict(p.as_str(), b"new", 0o600, None, None).unwrap();
+        assert_eq!(fs::read_to_string(p.as_str()).unwrap(), "new");
+        assert_eq!(fs::metadata(p.as_str()).unwrap().permissions().mode() & 0o777, 0o600);
+        fs::remove_file(p).unwrap();
+    }
+
+    #[test]
+    fn missing_directory() {
+        match write_atomic("/this/path/does/not/exist/config.toml", b"new"){
+            Err(err) => assert!(err.is_not_found()),
+            Ok(_) => panic!("Wrote into a nonexistent directory")
+        }
+    }
+}
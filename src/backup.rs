@@ -0,0 +1,230 @@
+//! Timestamped backups of the config.toml. Before `Config::write_changes`
+//! overwrites the file, a copy named `config.toml.<YYYYmmdd-HHMMSS>.bak` is
+//! stored next to it. Only the newest `Config::backup_count` backups are kept.
+//! Backups can be listed, compared against the current file and restored.
+use ::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+const SUFFIX: &str = ".bak";
+
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Backup{
+    /// The time the backup was made as `YYYYmmdd-HHMMSS` (UTC)
+    pub timestamp: String,
+    pub path: String
+}
+
+
+/// Return all backups of the config at `config_path`, newest first
+pub fn list_backups(config_path: &str) -> ConfigResult<Vec<Backup>>{
+    let target = PathBuf::from(config_path);
+    let name = match target.file_name(){
+        Some(n) => format!("{}.", n.to_string_lossy()),
+        None => return Ok(Vec::new())
+    };
+    let mut dir = target.clone();
+    dir.pop();
+    if dir.as_os_str().is_empty(){
+        dir.push(".");
+    }
+    let entries = match fs::read_dir(&dir){
+        Ok(e) => e,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(ConfigError::from_io(dir.to_string_lossy(), err))
+    };
+    let mut backups: Vec<Backup> = entries.filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let filename = e.file_name().to_string_lossy().to_string();
+            if !filename.starts_with(name.as_str()) || !filename.ends_with(SUFFIX){
+                return None;
+            }
+            let timestamp = filename[name.len()..filename.len()-SUFFIX.len()].to_string();
+            Some(Backup{ timestamp, path: e.path().to_string_lossy().to_string() })
+        })
+        .collect();
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+
+/// Copy the config at `config_path` to a new backup and delete all but the
+/// newest `keep` backups. Returns None if there is nothing to back up or
+/// `keep` is 0
+pub fn create_backup(config_path: &str, keep: usize) -> ConfigResult<Option<Backup>>{
+    if keep == 0 || !config_path.to_string().exists(){
+        return Ok(None);
+    }
+    // Backups made within the same second get a increasing counter, so they
+    // still sort in the order they were made
+    let base = timestamp(SystemTime::now());
+    let counter = list_backups(config_path)?.iter()
+                      .filter(|b| b.timestamp.starts_with(base.as_str()))
+                      .map(|b| b.timestamp[base.len()..].trim_start_matches('-').parse::<usize>().unwrap_or(0) + 1)
+                      .max();
+    let timestamp = match counter{
+        Some(n) => format!("{}-{:03}", base, n),
+        None => base
+    };
+    let path = format!("{}.{}{}", config_path, timestamp, SUFFIX);
+    fs::copy(config_path, path.as_str()).map_err(|err| ConfigError::from_io(path.as_str(), err))?;
+    rotate(config_path, keep)?;
+    Ok(Some(Backup{ timestamp, path }))
+}
+
+
+/// Delete all but the newest `keep` backups, return the deleted ones
+pub fn rotate(config_path: &str, keep: usize) -> ConfigResult<Vec<Backup>>{
+    let old: Vec<Backup> = list_backups(config_path)?.into_iter().skip(keep).collect();
+    for backup in &old{
+        fs::remove_file(backup.path.as_str()).map_err(|err| ConfigError::from_io(backup.path.as_str(), err))?;
+    }
+    Ok(old)
+}
+
+
+/// Return a line based diff between the backup and the config at
+/// `config_path`. Removed lines start with `-`, added lines with `+`
+pub fn diff_backup(backup: &Backup, config_path: &str) -> ConfigResult<String>{
+    let old = fs::read_to_string(backup.path.as_str()).map_err(|err| ConfigError::from_io(backup.path.as_str(), err))?;
+    let new = fs::read_to_string(config_path).map_err(|err| ConfigError::from_io(config_path, err))?;
+    Ok(diff_lines(old.as_str(), new.as_str()))
+}
+
+
+/// Replace the config at `config_path` with the backup. The current config is
+/// backed up first, so a restore can be undone as well
+pub fn restore_backup(backup: &Backup, config_path: &str, keep: usize) -> ConfigResult<()>{
+    let contents = fs::read(backup.path.as_str()).map_err(|err| ConfigError::from_io(backup.path.as_str(), err))?;
+    // Make sure the backup is a usable config before replacing anything
+    Format::from_path(config_path).parse(backup.path.as_str(), &String::from_utf8_lossy(&contents))?;
+    create_backup(config_path, keep.max(1))?;
+    atomic::write_atomic(config_path, &contents)
+}
+
+
+/// Format the time as `YYYYmmdd-HHMMSS` in UTC
+fn timestamp(time: SystemTime) -> String{
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rest) = (secs / 86400, secs % 86400);
+    // Convert days since epoch to a civil date (Howard Hinnants algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe/1460 + doe/36524 - doe/146_096) / 365;
+    let doy = doe - (365*yoe + yoe/4 - yoe/100);
+    let mp = (5*doy + 2) / 153;
+    let day = doy - (153*mp + 2)/5 + 1;
+    let month = if mp < 10 { mp + 3 }else{ mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 }else{ 0 };
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, rest/3600, rest%3600/60, rest%60)
+}
+
+
+/// A minimal line diff based on the longest common subsequence
+fn diff_lines(old: &str, new: &str) -> String{
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let mut lcs = vec![vec![0usize; b.len()+1]; a.len()+1];
+    for i in (0..a.len()).rev(){
+        for j in (0..b.len()).rev(){
+            lcs[i][j] = if a[i] == b[j] { lcs[i+1][j+1]+1 }else{ lcs[i+1][j].max(lcs[i][j+1]) };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len(){
+        if i < a.len() && j < b.len() && a[i] == b[j]{
+            out.push_str(format!(" {}\n", a[i]).as_str());
+            i += 1;
+            j += 1;
+        }else if i < a.len() && (j == b.len() || lcs[i+1][j] >= lcs[i][j+1]){
+            out.push_str(format!("-{}\n", a[i]).as_str());
+            i += 1;
+        }else{
+            out.push_str(format!("+{}\n", b[j]).as_str());
+            j += 1;
+        }
+    }
+    out
+}
+
+
+impl Config{
+    /// Return all backups of the config at `self.paths.config`, newest first
+    pub fn backups(&self) -> ConfigResult<Vec<Backup>>{
+        list_backups(self.paths.config.as_str())
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use backup::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000");
+        let t = UNIX_EPOCH + std::time::Duration::from_secs(1_709_210_096);
+        assert_eq!(timestamp(t), "20240229-123456");
+    }
+
+    #[test]
+    fn diff() {
+        assert_eq!(diff_lines("a\nb\nc\n", "a\nx\nc\n"), " a\n-b\n+x\n c\n");
+    }
+
+    #[test]
+    fn rotate_and_restore() {
+        let mut dir = std::env::temp_dir();
+        dir.push("bender_config_backups");
+        let dir = dir.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(dir.as_str()).unwrap();
+        let path = dir.push("config.toml");
+
+        let mut c = Config::default();
+        c.paths.config = path.clone();
+        c.backup_count = 2;
+        c.write_changes().unwrap();
+        assert!(c.backups().unwrap().is_empty());
+
+        for workload in 2..6{
+            c.worker.workload = workload;
+            c.write_changes().unwrap();
+        }
+        let backups = c.backups().unwrap();
+        assert_eq!(backups.len(), 2);
+        assert!(diff_backup(&backups[0], path.as_str()).unwrap().contains("+workload = 5"));
+
+        restore_backup(&backups[0], path.as_str(), 2).unwrap();
+        assert_eq!(Config::from_file(path.as_str()).unwrap().worker.workload, 4);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restore_other_formats() {
+        let mut dir = std::env::temp_dir();
+        dir.push("bender_config_backups_json");
+        let dir = dir.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(dir.as_str()).unwrap();
+        let path = dir.push("config.json");
+
+        let mut c = Config::default();
+        c.paths.config = path.clone();
+        c.write_changes().unwrap();
+        c.worker.workload = 2;
+        c.write_changes().unwrap();
+        let backups = c.backups().unwrap();
+        restore_backup(&backups[0], path.as_str(), 2).unwrap();
+        assert_eq!(Config::from_file(path.as_str()).unwrap().worker.workload, 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,350 @@
+//! The ConfigBuilder merges multiple partial configurations into one Config.
+//! The layers are applied in this order, later layers win:
+//! 1. the built-in defaults (`Config::default()`)
+//! 2. the system config (`/etc/bender/config.toml`)
+//! 3. drop-in files in `conf.d` next to the system config, sorted by name
+//! 4. a per-host override file (`hosts/<hostname>.toml` next to the system config)
+//! 5. `BENDER_*` environment variables (see the `overrides` module)
+//! 6. values set explicitly on the builder (e.g. from command line flags)
+//!
+//! Each file only needs to contain the values it wants to change. Missing
+//! files are skipped. If no layer sets `worker.id`, the id from the
+//! machine-id file (or one random id per builder) is used, so building twice
+//! gives the same Config. After building, the origin of every value can be
+//! looked up by its dotted key.
+use ::*;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use toml::Value;
+use toml::value::Table;
+
+
+/// The layer a value of the final Config came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layer{
+    Defaults,
+    System(String),
+    DropIn(String),
+    Host(String),
+    Environment,
+    CommandLine
+}
+
+impl fmt::Display for Layer{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            Layer::Defaults     => write!(f, "defaults"),
+            Layer::System(p)    => write!(f, "system config {}", p),
+            Layer::DropIn(p)    => write!(f, "drop-in {}", p),
+            Layer::Host(p)      => write!(f, "host config {}", p),
+            Layer::Environment  => write!(f, "environment"),
+            Layer::CommandLine  => write!(f, "command line")
+        }
+    }
+}
+
+
+/// Maps the dotted key of every value (e.g. `worker.workload`) to the layer
+/// it came from
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Origins(BTreeMap<String, Layer>);
+
+impl Origins{
+    /// Return the layer the value at the dotted key came from
+    pub fn get(&self, key: &str) -> Option<&Layer>{
+        self.0.get(key)
+    }
+
+    /// Iterate over all keys and their layers, sorted by key
+    pub fn iter(&self) -> std::collections::btree_map::Iter<'_, String, Layer>{
+        self.0.iter()
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ConfigBuilder{
+    system: Option<String>,
+    dropin_dir: Option<String>,
+    host_file: Option<String>,
+    env: bool,
+    values: Vec<(String, String)>,
+    // The default worker id, without a machine-id file Config::default() would
+    // pick a new one each time
+    worker_id: Uuid
+}
+
+
+impl Default for ConfigBuilder{
+    fn default() -> Self{
+        Self::new()
+    }
+}
+
+
+impl ConfigBuilder{
+    /// A builder using the standard locations next to `/etc/bender/config.toml`
+    pub fn new() -> Self{
+        Self::empty().system_file(Paths::default().config).env(true)
+    }
+
+    /// A builder that only uses the built-in defaults until layers are added
+    pub fn empty() -> Self{
+        Self{
+            system: None,
+            dropin_dir: None,
+            host_file: None,
+            env: false,
+            values: Vec::new(),
+            worker_id: machine_id::default_id()
+        }
+    }
+
+    /// Use the system config at the given path. This also sets the drop-in
+    /// directory (`conf.d`) and the per-host file (`hosts/<hostname>.toml`)
+    /// to the directory of the config
+    pub fn system_file<S>(mut self, path: S) -> Self where S: Into<String>{
+        let path = path.into();
+        let mut dir = PathBuf::from(path.as_str());
+        dir.pop();
+        let dir = dir.to_string_lossy().to_string();
+        self.dropin_dir = Some(dir.push("conf.d"));
+        self.host_file = hostname().map(|h| dir.push("hosts").push(format!("{}.toml", h)));
+        self.system = Some(path);
+        self
+    }
+
+    /// Read drop-in files from the given directory
+    pub fn dropin_dir<S>(mut self, path: S) -> Self where S: Into<String>{
+        self.dropin_dir = Some(path.into());
+        self
+    }
+
+    /// Use the given file as per-host override
+    pub fn host_file<S>(mut self, path: S) -> Self where S: Into<String>{
+        self.host_file = Some(path.into());
+        self
+    }
+
+    /// Enable or disable the `BENDER_*` environment variable layer
+    pub fn env(mut self, enabled: bool) -> Self{
+        self.env = enabled;
+        self
+    }
+
+    /// Set the value at the dotted key, this has the highest priority
+    pub fn set<K, V>(mut self, key: K, value: V) -> Self where K: Into<String>, V: Into<String>{
+        self.values.push((key.into(), value.into()));
+        self
+    }
+
+    /// Return the files and directories this builder reads from
+    pub fn watched_paths(&self) -> Vec<String>{
+        [&self.system, &self.dropin_dir, &self.host_file].iter()
+            .filter_map(|p| (*p).clone())
+            .collect()
+    }
+
+    /// Merge all layers into a Config
+    pub fn build(&self) -> ConfigResult<Config>{
+        let (config, _) = self.build_with_origins()?;
+        Ok(config)
+    }
+
+    /// Merge all layers into a Config and report where each value came from.
+    /// The resulting Config is validated, warnings are ignored
+    pub fn build_with_origins(&self) -> ConfigResult<(Config, Origins)>{
+        let mut defaults = Config::default();
+        defaults.worker.id = self.worker_id;
+        let mut root = Value::try_from(defaults)?;
+        let mut origins = Origins::default();
+        record(&root, "", &Layer::Defaults, &mut origins);
+
+        if let Some(ref path) = self.system{
+            if let Some(partial) = read_partial(path)?{
+                merge(&mut root, partial, "", &Layer::System(path.clone()), &mut origins);
+            }
+        }
+
+        for path in self.dropins()?{
+            if let Some(partial) = read_partial(path.as_str())?{
+                merge(&mut root, partial, "", &Layer::DropIn(path.clone()), &mut origins);
+            }
+        }
+
+        if let Some(ref path) = self.host_file{
+            if let Some(partial) = read_partial(path)?{
+                merge(&mut root, partial, "", &Layer::Host(path.clone()), &mut origins);
+            }
+        }
+
+        if self.env{
+            for (key, raw) in overrides::collect(&root, env::vars()){
+                keys::set_checked(&mut root, key.as_str(), raw.as_str())?;
+                origins.0.insert(key, Layer::Environment);
+            }
+        }
+
+        for (key, raw) in &self.values{
+            keys::set_checked(&mut root, key.as_str(), raw.as_str())?;
+            origins.0.insert(key.clone(), Layer::CommandLine);
+        }
+
+        let config: Config = root.try_into().map_err(|err| ConfigError::Parse("merged layers".to_string(), err))?;
+        config.ensure_valid()?;
+        Ok((config, origins))
+    }
+
+    /// Return the paths of all `*.toml` files in the drop-in directory,
+    /// sorted by name
+    fn dropins(&self) -> ConfigResult<Vec<String>>{
+        let dir = match self.dropin_dir{
+            Some(ref d) if d.exists() => d,
+            _ => return Ok(Vec::new())
+        };
+        let entries = fs::read_dir(dir).map_err(|err| ConfigError::from_io(dir.as_str(), err))?;
+        let mut paths: Vec<String> = entries.filter_map(|e| e.ok())
+                                            .map(|e| e.path())
+                                            .filter(|p| p.is_file() && p.extension() == Some("toml".as_ref()))
+                                            .map(|p| p.to_string_lossy().to_string())
+                                            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+
+/// Return the hostname of this machine
+fn hostname() -> Option<String>{
+    let name = match env::var("HOSTNAME"){
+        Ok(h) => h,
+        Err(_) => fs::read_to_string("/etc/hostname").ok()?
+    };
+    let name = name.trim().to_string();
+    if name.is_empty(){ None }else{ Some(name) }
+}
+
+
+/// Read a partial config as toml Value and migrate it to the current version
+/// without adding defaults. Returns Ok(None) if the file doesn't exist
+fn read_partial(path: &str) -> ConfigResult<Option<Value>>{
+    let contents = match fs::read_to_string(path){
+        Ok(c) => c,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(ConfigError::from_io(path, err))
+    };
+    let mut table: Table = toml::from_str(contents.as_str()).map_err(|err| ConfigError::Parse(path.to_string(), err))?;
+    migration::migrate_partial(&mut table)?;
+    Ok(Some(Value::Table(table)))
+}
+
+
+/// Join a dotted key prefix and a key
+fn join(prefix: &str, key: &str) -> String{
+    if prefix.is_empty(){ key.to_string() }else{ format!("{}.{}", prefix, key) }
+}
+
+
+/// Record the layer for every non-table value below `value`
+fn record(value: &Value, prefix: &str, layer: &Layer, origins: &mut Origins){
+    match value.as_table(){
+        Some(table) => {
+            for (k, v) in table{
+                record(v, join(prefix, k).as_str(), layer, origins);
+            }
+        },
+        None => { origins.0.insert(prefix.to_string(), layer.clone()); }
+    }
+}
+
+
+/// Merge `overlay` into `base`. Tables are merged recursively, everything
+/// else is replaced
+fn merge(base: &mut Value, overlay: Value, prefix: &str, layer: &Layer, origins: &mut Origins){
+    match (base, overlay){
+        (&mut Value::Table(ref mut base), Value::Table(overlay)) => {
+            for (k, v) in overlay{
+                let key = join(prefix, k.as_str());
+                match base.get_mut(k.as_str()){
+                    Some(existing) if existing.is_table() && v.is_table() => {
+                        merge(existing, v, key.as_str(), layer, origins);
+                        continue;
+                    },
+                    _ => ()
+                }
+                record(&v, key.as_str(), layer, origins);
+                base.insert(k, v);
+            }
+        },
+        (base, overlay) => {
+            record(&overlay, prefix, layer, origins);
+            *base = overlay;
+        }
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use builder::*;
+
+    #[test]
+    fn defaults_only() {
+        let (c, origins) = ConfigBuilder::empty().build_with_origins().unwrap();
+        let mut d = Config::default();
+        d.worker.id = c.worker.id;
+        assert_eq!(c, d);
+        assert_eq!(origins.get("worker.workload"), Some(&Layer::Defaults));
+    }
+
+    #[test]
+    fn stable_worker_id() {
+        let builder = ConfigBuilder::empty();
+        assert_eq!(builder.build().unwrap().worker.id, builder.build().unwrap().worker.id);
+    }
+
+    #[test]
+    fn layers() {
+        let mut dir = env::temp_dir();
+        dir.push("bender_config_builder_layers");
+        let dir = dir.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(dir.push("conf.d")).unwrap();
+        let system = dir.push("config.toml");
+        let dropin = dir.push("conf.d").push("10-worker.toml");
+        let host = dir.push("host.toml");
+        fs::write(system.as_str(), "version = 5\nservername = \"system\"\nbackup_count = 10\n[worker]\nworkload = 2\n").unwrap();
+        // Unversioned and with a legacy key, but nothing else may be touched
+        fs::write(dropin.as_str(), "[worker]\nworkload = 3\nheart_rate_seconds = 5\n").unwrap();
+        fs::write(dir.push("conf.d").push("README"), "not a drop-in").unwrap();
+        fs::write(host.as_str(), "[rabbitmq]\nurl = \"amqp://host//\"\n").unwrap();
+
+        let (c, origins) = ConfigBuilder::empty()
+                                         .system_file(system.as_str())
+                                         .host_file(host.as_str())
+                                         .set("janitor.checking_period", "5s")
+                                         .build_with_origins()
+                                         .unwrap();
+        assert_eq!(c.servername, "system");
+        assert_eq!(c.worker.workload, 3);
+        assert_eq!(c.worker.heart_rate, HumanDuration::from_secs(5));
+        assert_eq!(c.backup_count, 10);
+        assert_eq!(c.version, CURRENT_VERSION);
+        assert_eq!(c.rabbitmq.url, "amqp://host//");
+        assert_eq!(c.janitor.checking_period, HumanDuration::from_secs(5));
+        assert_eq!(origins.get("servername"), Some(&Layer::System(system.clone())));
+        assert_eq!(origins.get("backup_count"), Some(&Layer::System(system.clone())));
+        assert_eq!(origins.get("version"), Some(&Layer::Defaults));
+        assert_eq!(origins.get("worker.workload"), Some(&Layer::DropIn(dropin.clone())));
+        assert_eq!(origins.get("paths.private_owner"), Some(&Layer::Defaults));
+        assert_eq!(origins.get("rabbitmq.url"), Some(&Layer::Host(host.clone())));
+        assert_eq!(origins.get("janitor.checking_period"), Some(&Layer::CommandLine));
+        assert_eq!(origins.get("worker.disklimit"), Some(&Layer::Defaults));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+//! Field level comparison of two Configs. Every value that differs is
+//! reported as a Change with its dotted key, the old and the new value.
+//! Hot reload subscribers can use `Change::section()` to decide which parts
+//! of a service need a restart, `unified()` renders the changes for humans.
+use ::*;
+use std::fmt;
+use toml::Value;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change{
+    pub key: String,
+    pub old: ConfigValue,
+    pub new: ConfigValue
+}
+
+impl Change{
+    /// The section the value belongs to (e.g. `worker`), empty for top level values
+    pub fn section(&self) -> &str{
+        match self.key.rfind('.'){
+            Some(i) => &self.key[..i],
+            None => ""
+        }
+    }
+
+    /// The name of the value within its section (e.g. `workload`)
+    pub fn field(&self) -> &str{
+        match self.key.rfind('.'){
+            Some(i) => &self.key[i+1..],
+            None => self.key.as_str()
+        }
+    }
+}
+
+impl fmt::Display for Change{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}: {} -> {}", self.key, self.old, self.new)
+    }
+}
+
+
+impl Config{
+    /// Return every value that differs between self (old) and other (new),
+    /// sorted by key
+    pub fn diff(&self, other: &Config) -> Vec<Change>{
+        let mut changes = Vec::new();
+        for key in self.keys(){
+            if let (Ok(old), Ok(new)) = (self.get_key(key.as_str()), other.get_key(key.as_str())){
+                if old != new{
+                    changes.push(Change{ key, old, new });
+                }
+            }
+        }
+        changes.sort_by(|a, b| a.key.cmp(&b.key));
+        changes
+    }
+}
+
+
+/// Render the changes like a unified diff of the TOML files, grouped by
+/// section. `old_label` and `new_label` name the compared configs
+pub fn unified(changes: &[Change], old_label: &str, new_label: &str) -> String{
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    // Top level values have to come before the first section header
+    let mut sorted: Vec<&Change> = changes.iter().collect();
+    sorted.sort_by_key(|c| c.section());
+    let mut section = None;
+    for change in sorted{
+        if section != Some(change.section()){
+            section = Some(change.section());
+            if !change.section().is_empty(){
+                out.push_str(format!(" [{}]\n", change.section()).as_str());
+            }
+        }
+        out.push_str(format!("-{} = {}\n", change.field(), literal(&change.old)).as_str());
+        out.push_str(format!("+{} = {}\n", change.field(), literal(&change.new)).as_str());
+    }
+    out
+}
+
+
+/// Format the value as TOML literal (strings are quoted)
+fn literal(value: &ConfigValue) -> String{
+    match value{
+        ConfigValue::String(s)  => Value::String(s.clone()).to_string(),
+        ConfigValue::Integer(i) => i.to_string(),
+        ConfigValue::Float(x)   => Value::Float(*x).to_string(),
+        ConfigValue::Boolean(b) => b.to_string()
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use diff::*;
+
+    #[test]
+    fn changed_fields() {
+        let old = Config::default();
+        let mut new = old.clone();
+        assert!(old.diff(&new).is_empty());
+        new.worker.workload = 3;
+        new.servername = "render.example".to_string();
+        let changes = old.diff(&new);
+        assert_eq!(changes, vec![
+            Change{ key: "servername".to_string(),
+                    old: ConfigValue::String("bender.render".to_string()),
+                    new: ConfigValue::String("render.example".to_string()) },
+            Change{ key: "worker.workload".to_string(),
+                    old: ConfigValue::Integer(1),
+                    new: ConfigValue::Integer(3) }
+        ]);
+        assert_eq!(changes[1].section(), "worker");
+        assert_eq!(changes[0].section(), "");
+    }
+
+    #[test]
+    fn unified_view() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.servername = "render.example".to_string();
+        new.janitor.checking_period = HumanDuration::from_secs(120);
+        new.worker.workload = 3;
+        assert_eq!(unified(&old.diff(&new), "a.toml", "b.toml"),
+                   "--- a.toml\n+++ b.toml\n\
+                    -servername = \"bender.render\"\n+servername = \"render.example\"\n\
+                    \x20[janitor]\n-checking_period = \"1m\"\n+checking_period = \"2m\"\n\
+                    \x20[worker]\n-workload = 1\n+workload = 3\n");
+    }
+}
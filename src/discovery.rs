@@ -0,0 +1,132 @@
+//! Locates the config.toml without relying on any external tool. The
+//! locations are tried in this order:
+//! 1. a explicitly given path (e.g. from a command line flag)
+//! 2. the path stored in the `BENDER_CONFIG` environment variable
+//! 3. the users config directory (`$XDG_CONFIG_HOME/bender/config.toml` or
+//!    `~/.config/bender/config.toml`)
+//! 4. the system wide default `/etc/bender/config.toml`
+//!
+//! If a path is given explicitly or via `BENDER_CONFIG` it has to exist, the
+//! search won't silently fall back to another config in that case.
+use ::*;
+use std::env;
+use std::fmt;
+
+
+/// Name of the environment variable that points to a config.toml
+pub const ENV_VAR: &str = "BENDER_CONFIG";
+
+
+/// Where a discovered config.toml came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource{
+    Explicit,
+    Environment,
+    UserConfigDir,
+    System
+}
+
+impl fmt::Display for ConfigSource{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ConfigSource::Explicit      => write!(f, "explicit path"),
+            ConfigSource::Environment   => write!(f, "{} environment variable", ENV_VAR),
+            ConfigSource::UserConfigDir => write!(f, "user config directory"),
+            ConfigSource::System        => write!(f, "system default")
+        }
+    }
+}
+
+
+/// The result of a successful discovery
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discovered{
+    pub path: String,
+    pub source: ConfigSource
+}
+
+
+/// Return the location of the config.toml in the users config directory, if
+/// a config directory can be determined
+pub fn user_config_path() -> Option<String>{
+    let mut p = match env::var_os("XDG_CONFIG_HOME"){
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let mut home = PathBuf::from(env::var_os("HOME")?);
+            home.push(".config");
+            home
+        }
+    };
+    p.push("bender");
+    p.push("config.toml");
+    p.to_str().map(|s| s.to_string())
+}
+
+
+/// Run through the discovery chain and return the first config.toml found
+pub fn discover(explicit: Option<&str>) -> ConfigResult<Discovered>{
+    if let Some(p) = explicit{
+        return existing(p.trim().to_string(), ConfigSource::Explicit);
+    }
+
+    if let Ok(p) = env::var(ENV_VAR){
+        if !p.trim().is_empty(){
+            return existing(p.trim().to_string(), ConfigSource::Environment);
+        }
+    }
+
+    let mut searched = Vec::new();
+    if let Some(p) = user_config_path(){
+        if p.exists(){
+            return Ok(Discovered{ path: p, source: ConfigSource::UserConfigDir });
+        }
+        searched.push(p);
+    }
+
+    let p = Paths::default().config;
+    if p.exists(){
+        return Ok(Discovered{ path: p, source: ConfigSource::System });
+    }
+    searched.push(p);
+
+    Err(ConfigError::NotDiscovered(searched))
+}
+
+
+fn existing(path: String, source: ConfigSource) -> ConfigResult<Discovered>{
+    if path.exists(){
+        Ok(Discovered{ path, source })
+    }else{
+        Err(ConfigError::NotFound(path))
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use discovery::*;
+
+    #[test]
+    fn explicit_path() {
+        let mut p = env::temp_dir();
+        p.push("bender_config_discovery_explicit.toml");
+        let p = p.to_str().unwrap().to_string();
+        fs::write(p.as_str(), "").unwrap();
+        let d = discover(Some(p.as_str())).unwrap();
+        assert_eq!(d.source, ConfigSource::Explicit);
+        assert_eq!(d.path, p);
+        fs::remove_file(p).unwrap();
+    }
+
+    #[test]
+    fn explicit_path_missing() {
+        match discover(Some("/this/path/does/not/exist/config.toml")){
+            Err(err) => assert!(err.is_not_found()),
+            Ok(d) => panic!("Discovered a nonexistent config: {:?}", d)
+        }
+    }
+}
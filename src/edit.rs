@@ -0,0 +1,195 @@
+//! Writes a Config into an existing config.toml without destroying what the
+//! operator did to the file. Only values that actually changed are replaced,
+//! comments, the order of keys and blank lines are kept. Changed values that
+//! are missing from the file are appended to their section, keys renamed by a
+//! migration are replaced by their new name. Everything else (e.g. keys the
+//! operator added) is left alone.
+use ::*;
+use toml::Value;
+use toml::value::Table;
+use toml_edit::{Document, Item};
+
+
+/// Return the text of `original` with all values changed to match `config`
+pub fn update_document(original: &str, config: &Config) -> ConfigResult<String>{
+    let raw: Table = toml::from_str(original)
+                         .map_err(|err| ConfigError::Parse("existing document".to_string(), err))?;
+    let (mut old, _) = migration::parse_table("existing document", raw.clone())?;
+    // The location of the config.toml is taken from where it was found, so
+    // it's only a change if the document names one
+    if raw.get("paths").and_then(|p| p.get("config")).is_none(){
+        old.paths.config = config.paths.config.clone();
+    }
+    let mut document = original.parse::<Document>()
+                               .map_err(|err| ConfigError::Edit(err.to_string()))?;
+
+    // The version is always written, so the file isn't migrated again
+    let mut forced = vec!["version".to_string()];
+    for (section, legacy, replacement) in migration::renamed_keys(){
+        let removed = document.as_table_mut()
+                              .get_mut(section)
+                              .and_then(|item| item.as_table_mut())
+                              .and_then(|table| table.remove(legacy));
+        if removed.is_some(){
+            forced.push(format!("{}.{}", section, replacement));
+        }
+    }
+
+    update_table(document.as_table_mut(), "", Some(&raw), Some(&to_table(&old)?), &to_table(config)?, &forced);
+    Ok(document.to_string())
+}
+
+
+/// Serialize a Config into a toml Table
+fn to_table(config: &Config) -> ConfigResult<Table>{
+    match Value::try_from(config)?{
+        Value::Table(t) => Ok(t),
+        _ => unreachable!("a Config always serializes to a table")
+    }
+}
+
+
+/// Update the values of `doc` (the table at the dotted key `prefix`) to match
+/// `new`. `old` holds the values the Config had before and `raw` the values as
+/// they are written in the document. Only values that changed, that are
+/// written differently in the document (e.g. in a legacy format) or that are
+/// `forced` are touched
+fn update_table(doc: &mut toml_edit::Table, prefix: &str, raw: Option<&Table>, old: Option<&Table>, new: &Table, forced: &[String]){
+    for (key, value) in new{
+        let dotted = if prefix.is_empty(){ key.clone() }else{ format!("{}.{}", prefix, key) };
+        let raw_value = raw.and_then(|r| r.get(key));
+        let old_value = old.and_then(|o| o.get(key));
+        match value{
+            Value::Table(new_table) => {
+                let raw_table = raw_value.and_then(|v| v.as_table());
+                let old_table = old_value.and_then(|v| v.as_table());
+                match doc.get_mut(key.as_str()){
+                    Some(item) => match item.as_table_mut(){
+                        Some(t) => update_table(t, dotted.as_str(), raw_table, old_table, new_table, forced),
+                        None => *item = Item::Value(to_edit_value(value))
+                    },
+                    None => {
+                        // Only add sections that get any values
+                        let mut t = toml_edit::Table::new();
+                        update_table(&mut t, dotted.as_str(), raw_table, old_table, new_table, forced);
+                        if !t.is_empty(){
+                            doc.insert(key.as_str(), Item::Table(t));
+                        }
+                    }
+                }
+            },
+            leaf => {
+                let changed = old_value != Some(leaf);
+                let stale = match raw_value{
+                    Some(r) => r != leaf,
+                    None => forced.contains(&dotted)
+                };
+                if !changed && !stale{
+                    continue;
+                }
+                let mut replacement = to_edit_value(leaf);
+                match doc.get_mut(key.as_str()){
+                    Some(item) => {
+                        if let Some(existing) = item.as_value(){
+                            *replacement.decor_mut() = existing.decor().clone();
+                        }
+                        *item = Item::Value(replacement);
+                    },
+                    None => { doc.insert(key.as_str(), Item::Value(replacement)); }
+                }
+            }
+        }
+    }
+}
+
+
+/// Convert a toml Value into the equivalent toml_edit Value
+fn to_edit_value(value: &Value) -> toml_edit::Value{
+    match value{
+        Value::String(s)   => s.as_str().into(),
+        Value::Integer(i)  => (*i).into(),
+        Value::Float(f)    => (*f).into(),
+        Value::Boolean(b)  => (*b).into(),
+        Value::Datetime(d) => d.to_string().parse().expect("a valid toml datetime is a valid toml_edit value"),
+        Value::Array(a)    => toml_edit::Value::Array(a.iter().map(to_edit_value).collect()),
+        Value::Table(t)    => toml_edit::Value::InlineTable(t.iter().map(|(k, v)| (k.as_str(), to_edit_value(v))).collect())
+    }
+}
+
+
+impl Config{
+    /// Serialize the Config to a file. If the file already exists only the
+    /// changed values are replaced, so comments and formatting survive. The
+    /// file is replaced atomically. Files in other formats than TOML are
+    /// rewritten completely
+    pub fn update_file<S>(&self, path: S) -> ConfigResult<()> where S: Into<String>{
+        let path = path.into();
+        if Format::from_path(path.as_str()) != Format::Toml{
+            return self.to_file(path);
+        }
+        let original = match fs::read_to_string(path.as_str()){
+            Ok(o) => o,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return self.to_file(path),
+            Err(err) => return Err(ConfigError::from_io(path, err))
+        };
+        let updated = update_document(original.as_str(), self)?;
+        atomic::write_atomic(path, updated.as_bytes())
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use edit::*;
+
+    const ORIGINAL: &str = "# Managed by ansible, edit with care
+servername = \"bender.render\"
+
+[worker]
+# how many frames at once
+workload = 1 # keep this low on the server
+
+disklimit = 2
+";
+
+    #[test]
+    fn keeps_comments() {
+        let mut c = Config::deserialize(ORIGINAL).unwrap();
+        c.worker.workload = 4;
+        let updated = update_document(ORIGINAL, &c).unwrap();
+        assert!(updated.starts_with("# Managed by ansible, edit with care\nservername = \"bender.render\"\n"));
+        assert!(updated.contains("# how many frames at once\nworkload = 4 # keep this low on the server\n\ndisklimit = \"2GB\"\n"));
+        assert_eq!(Config::deserialize(updated).unwrap(), c);
+    }
+
+    #[test]
+    fn replaces_migrated_keys() {
+        let legacy = "version = 2\n[janitor]\n# check often\nchecking_period_seconds = 90\n";
+        let (c, _) = migration::parse("test", legacy).unwrap();
+        let updated = update_document(legacy, &c).unwrap();
+        assert!(!updated.contains("checking_period_seconds"));
+        assert!(updated.contains("checking_period = \"1m30s\"\n"));
+        assert!(updated.starts_with(format!("version = {}\n", CURRENT_VERSION).as_str()));
+        assert_eq!(Config::deserialize(updated).unwrap(), c);
+    }
+
+    #[test]
+    fn partial_documents_stay_partial() {
+        let partial = format!("version = {}\nservername = \"a\"\n[worker]\nid = \"9f1c3c52-6f4e-4a7b-9a36-2d7c1c0e5b11\"\nlocal_tweak = true\n", CURRENT_VERSION);
+        let mut c = Config::deserialize(partial.as_str()).unwrap();
+        c.servername = "b".to_string();
+        let updated = update_document(partial.as_str(), &c).unwrap();
+        assert_eq!(updated, partial.replace("\"a\"", "\"b\""));
+    }
+
+    #[test]
+    fn unchanged_is_identical_for_complete_documents() {
+        let c = Config::default();
+        let serialized = c.serialize().unwrap();
+        assert_eq!(update_document(serialized.as_str(), &c).unwrap(), serialized);
+    }
+}
@@ -0,0 +1,127 @@
+//! The error type used by all fallible functions of bender_config. Each
+//! variant corresponds to one distinct way reading, writing or locating the
+//! configuration can fail, so services can react to them without having to
+//! match on error messages.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use toml;
+use validation::Issue;
+use migration::CURRENT_VERSION;
+
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
+
+
+#[derive(Debug)]
+pub enum ConfigError{
+    /// No config.toml was found at any of the searched paths
+    NotDiscovered(Vec<String>),
+    /// There is no file at the given path
+    NotFound(String),
+    /// The file or directory at the given path could not be accessed
+    PermissionDenied(String, io::Error),
+    /// Any other IO error that occured while accessing the given path
+    Io(String, io::Error),
+    /// The TOML read from the given origin is invalid or doesn't match the Config
+    Parse(String, toml::de::Error),
+    /// The Config could not be serialized to TOML
+    Serialize(toml::ser::Error),
+    /// The JSON, YAML or env document read from the given origin is invalid:
+    /// (origin, reason)
+    Decode(String, String),
+    /// The Config could not be serialized to JSON or YAML
+    Encode(String),
+    /// The dotted key (e.g. `worker.workload`) doesn't exist in the Config
+    UnknownKey(String),
+    /// The value for the dotted key couldn't be parsed: (key, value, reason)
+    InvalidValue(String, String, String),
+    /// The Config failed validation, contains all issues (including warnings)
+    Invalid(Vec<Issue>),
+    /// The config was written by a newer version of bender_config
+    UnsupportedVersion(u32),
+    /// The existing config document couldn't be edited in place
+    Edit(String),
+    /// The config files couldn't be watched for changes
+    Watch(String)
+}
+
+
+impl ConfigError{
+    /// Classify a io::Error that occured while accessing `path`
+    pub fn from_io<S>(path: S, err: io::Error) -> Self where S: Into<String>{
+        let path = path.into();
+        match err.kind(){
+            io::ErrorKind::NotFound         => ConfigError::NotFound(path),
+            io::ErrorKind::PermissionDenied => ConfigError::PermissionDenied(path, err),
+            _                               => ConfigError::Io(path, err)
+        }
+    }
+
+    /// Returns true if the error was caused by a missing file
+    pub fn is_not_found(&self) -> bool{
+        matches!(self, ConfigError::NotFound(_) | ConfigError::NotDiscovered(_))
+    }
+
+    /// Returns true if the error was caused by missing permissions
+    pub fn is_permission_denied(&self) -> bool{
+        matches!(self, ConfigError::PermissionDenied(_, _))
+    }
+}
+
+
+impl fmt::Display for ConfigError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ConfigError::NotDiscovered(searched)  => write!(f, "Didn't find a config.toml, searched: {}", searched.join(", ")),
+            ConfigError::NotFound(path)           => write!(f, "There is no file at path {}", path),
+            ConfigError::PermissionDenied(path, _)=> write!(f, "Permission denied while accessing {}", path),
+            ConfigError::Io(path, err)            => write!(f, "IO error while accessing {}: {}", path, err),
+            ConfigError::Parse(path, err)         => write!(f, "Error while deserializing the configuration from {}: {}", path, err),
+            ConfigError::Serialize(err)           => write!(f, "Error while serializing the configuration: {}", err),
+            ConfigError::Decode(origin, reason)   => write!(f, "Error while deserializing the configuration from {}: {}", origin, reason),
+            ConfigError::Encode(reason)           => write!(f, "Error while serializing the configuration: {}", reason),
+            ConfigError::UnknownKey(key)          => write!(f, "There is no config value called {}", key),
+            ConfigError::InvalidValue(key, value, reason) => write!(f, "Invalid value \"{}\" for {}: {}", value, key, reason),
+            ConfigError::Invalid(issues)          => {
+                let errors: Vec<String> = issues.iter()
+                                                .filter(|i| i.is_error())
+                                                .map(|i| i.to_string())
+                                                .collect();
+                write!(f, "The configuration is invalid: {}", errors.join("; "))
+            },
+            ConfigError::UnsupportedVersion(v)    => write!(f, "The config has version {}, but this version of bender_config only supports up to version {}", v, CURRENT_VERSION),
+            ConfigError::Edit(message)            => write!(f, "Couldn't edit the existing config document: {}", message),
+            ConfigError::Watch(message)           => write!(f, "Couldn't watch the config for changes: {}", message)
+        }
+    }
+}
+
+
+impl Error for ConfigError{
+    fn source(&self) -> Option<&(dyn Error + 'static)>{
+        match self{
+            ConfigError::NotDiscovered(_)         => None,
+            ConfigError::NotFound(_)              => None,
+            ConfigError::PermissionDenied(_, err) => Some(err),
+            ConfigError::Io(_, err)               => Some(err),
+            ConfigError::Parse(_, err)            => Some(err),
+            ConfigError::Serialize(err)           => Some(err),
+            ConfigError::Decode(_, _)             => None,
+            ConfigError::Encode(_)                => None,
+            ConfigError::UnknownKey(_)            => None,
+            ConfigError::InvalidValue(_, _, _)    => None,
+            ConfigError::Invalid(_)               => None,
+            ConfigError::UnsupportedVersion(_)    => None,
+            ConfigError::Edit(_)                  => None,
+            ConfigError::Watch(_)                 => None
+        }
+    }
+}
+
+
+impl From<toml::ser::Error> for ConfigError{
+    fn from(err: toml::ser::Error) -> Self{
+        ConfigError::Serialize(err)
+    }
+}
@@ -0,0 +1,157 @@
+//! Settings module for flaskbender. Instead of calling the CLI, the Flask app
+//! loads a generated python module with everything it needs from the config:
+//! ```ignore
+//! app.config.from_pyfile("/etc/bender/flaskbender_settings.py")
+//! ```
+//! The upload limit is written as `MAX_CONTENT_LENGTH` in bytes, the app
+//! secret is read from its file when the module is loaded. A previous secret
+//! that is still within `flaskbender.secret_overlap` of its rotation ends up
+//! in `SECRET_KEY_FALLBACKS`, so flaskbender has to be restarted after a
+//! rotation. A long running `keep_updated()` regenerates the module whenever
+//! the config changes.
+use ::*;
+use std::time::Duration;
+
+
+/// The default location of the generated settings module
+pub const DEFAULT_PATH: &str = "/etc/bender/flaskbender_settings.py";
+
+
+/// Render a str as python string literal (JSON strings are valid python)
+fn py_str(s: &str) -> String{
+    serde_json::to_string(s).expect("a str always serializes")
+}
+
+
+impl Config{
+    /// Render the settings flaskbender needs as python module. Flask only
+    /// picks up the uppercase names
+    pub fn flask_settings(&self) -> String{
+        let lines = vec![
+            "# Flask settings for flaskbender, generated by bender-config. Don't edit".to_string(),
+            "# this file, changes will be overwritten.".to_string(),
+            String::new(),
+            "import os".to_string(),
+            "import time".to_string(),
+            String::new(),
+            String::new(),
+            "def _read(path):".to_string(),
+            "    with open(path) as f:".to_string(),
+            "        return f.read()".to_string(),
+            String::new(),
+            String::new(),
+            "def _read_previous(path, overlap):".to_string(),
+            "    try:".to_string(),
+            "        if time.time() - os.path.getmtime(path) < overlap:".to_string(),
+            "            return [_read(path)]".to_string(),
+            "    except OSError:".to_string(),
+            "        pass".to_string(),
+            "    return []".to_string(),
+            String::new(),
+            String::new(),
+            format!("BENDER_SERVERNAME = {}", py_str(self.servername.as_str())),
+            format!("# flaskbender.upload_limit = {}", self.flaskbender.upload_limit),
+            format!("MAX_CONTENT_LENGTH = {}", self.flaskbender.upload_limit.as_bytes()),
+            format!("UPLOAD_URL = {}", py_str(self.flaskbender.upload_url.as_str())),
+            format!("JOB_COOKIE_NAME = {}", py_str(self.flaskbender.job_cookie_name.as_str())),
+            format!("UPLOAD_FOLDER = {}", py_str(self.paths.upload.as_str())),
+            format!("BLENDFILES_FOLDER = {}", py_str(self.paths.blend().as_str())),
+            format!("FRAMES_FOLDER = {}", py_str(self.paths.frames().as_str())),
+            format!("AMQP_URL = {}", py_str(self.rabbitmq.url.as_str())),
+            String::new(),
+            "# The secret itself never ends up in this file".to_string(),
+            format!("SECRET_KEY_FILE = {}", py_str(self.get_appsecret_path().as_str())),
+            "SECRET_KEY = _read(SECRET_KEY_FILE)".to_string(),
+            format!("PREVIOUS_SECRET_KEY_FILE = {}", py_str(self.get_previous_appsecret_path().as_str())),
+            format!("# flaskbender.secret_overlap = {}", self.flaskbender.secret_overlap),
+            format!("SECRET_KEY_OVERLAP = {}", self.flaskbender.secret_overlap.as_secs()),
+            "SECRET_KEY_FALLBACKS = _read_previous(PREVIOUS_SECRET_KEY_FILE, SECRET_KEY_OVERLAP)".to_string(),
+            String::new()
+        ];
+        lines.join("\n")
+    }
+
+    /// Write the settings module to `path`, unless it is already up to date.
+    /// Returns true if the file was written
+    pub fn write_flask_settings<S>(&self, path: S) -> ConfigResult<bool> where S: Into<String>{
+        let path = path.into();
+        let settings = self.flask_settings();
+        match fs::read_to_string(path.as_str()){
+            Ok(ref existing) if *existing == settings => return Ok(false),
+            Ok(_) => (),
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => return Err(ConfigError::from_io(path, err))
+        }
+        atomic::write_atomic(path, settings.as_bytes())?;
+        Ok(true)
+    }
+}
+
+
+/// Write the settings module to `path` and regenerate it whenever the config
+/// `builder` builds changes. `on_update` gets the result of every attempt:
+/// true if the module was written, false if the change didn't affect it, or
+/// the error (including rejected reloads, then the module is left as it is).
+/// Regeneration stops when the returned watcher is dropped
+pub fn keep_updated<S, F>(builder: ConfigBuilder, path: S, debounce: Duration, mut on_update: F) -> ConfigResult<ConfigWatcher>
+    where S: Into<String>, F: FnMut(ConfigResult<bool>) + Send + 'static{
+    let path = path.into();
+    let target = path.clone();
+    let watcher = ConfigWatcher::with_callback(builder, debounce, move |event| {
+        match event{
+            ReloadEvent::Reloaded(config, _) => on_update(config.write_flask_settings(target.as_str())),
+            ReloadEvent::Rejected(err) => on_update(Err(err))
+        }
+    })?;
+    watcher.current().write_flask_settings(path)?;
+    Ok(watcher)
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use flask::*;
+
+    #[test]
+    fn render_settings() {
+        let mut c = Config::default();
+        c.flaskbender.upload_limit = "500MB".parse().unwrap();
+        c.servername = "Render \"farm\"".to_string();
+        let settings = c.flask_settings();
+        assert!(settings.contains("\nMAX_CONTENT_LENGTH = 500000000\n"));
+        assert!(settings.contains("\nJOB_COOKIE_NAME = \"bender-renderjobs\"\n"));
+        assert!(settings.contains("\nAMQP_URL = \"amqp://localhost//\"\n"));
+        assert!(settings.contains("\nBENDER_SERVERNAME = \"Render \\\"farm\\\"\"\n"));
+        assert!(settings.contains("\nSECRET_KEY_FILE = \"/var/lib/flask/private/app.secret\"\nSECRET_KEY = _read(SECRET_KEY_FILE)\n"));
+        assert!(settings.contains("\nSECRET_KEY_OVERLAP = 604800\n"));
+        assert!(!settings.contains(&c.worker.id.to_string()));
+    }
+
+    #[test]
+    fn regenerate_on_change() {
+        let mut dir = std::env::temp_dir();
+        dir.push("bender_config_flask");
+        let dir = dir.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(dir.as_str()).unwrap();
+        let config = dir.push("config.toml");
+        let settings = dir.push("settings.py");
+        fs::write(config.as_str(), "[flaskbender]\nupload_limit = \"1GB\"\n").unwrap();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let builder = ConfigBuilder::empty().system_file(config.as_str());
+        let watcher = keep_updated(builder, settings.as_str(), Duration::from_millis(100), move |r| { let _ = tx.send(r.map_err(|e| e.to_string())); }).unwrap();
+        assert!(fs::read_to_string(settings.as_str()).unwrap().contains("MAX_CONTENT_LENGTH = 1000000000\n"));
+        assert!(!watcher.current().write_flask_settings(settings.as_str()).unwrap());
+
+        fs::write(config.as_str(), "[flaskbender]\nupload_limit = \"2GiB\"\n").unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(Ok(true)));
+        assert!(fs::read_to_string(settings.as_str()).unwrap().contains("MAX_CONTENT_LENGTH = 2147483648\n"));
+        drop(watcher);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
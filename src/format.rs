@@ -0,0 +1,275 @@
+//! Besides TOML the Config can be read and written as JSON, YAML or as env
+//! file with one `BENDER_*` variable per value (named like the overrides of
+//! the `overrides` module), which is handy for the python flaskbender and
+//! for shell scripts:
+//! ```ignore
+//! let config = Config::from_file("/etc/bender/config.toml")?;
+//! config.to_file("/run/bender/config.json")?;
+//! config.to_file("/run/bender/bender.env")?;
+//! let env = config.serialize_as(Format::Env)?;
+//! ```
+//! `from_file()` and `to_file()` pick the format by the file extension,
+//! everything that is not `.json`, `.yaml`, `.yml` or `.env` is TOML.
+use ::*;
+use std::fmt;
+use std::str::FromStr;
+use toml::Value;
+use toml::value::Table;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format{
+    Toml,
+    Json,
+    Yaml,
+    /// `BENDER_WORKER__WORKLOAD=1` lines, as read by dotenv or a shell
+    Env
+}
+
+
+impl Format{
+    /// Detect the format of a file by its extension
+    pub fn from_path<S>(path: S) -> Self where S: Into<String>{
+        let path = PathBuf::from(path.into());
+        let extension = match path.extension(){
+            Some(e) => e.to_string_lossy().to_lowercase(),
+            // A file called ".env" has no extension
+            None => path.file_name().map(|n| n.to_string_lossy().trim_start_matches('.').to_lowercase())
+                                    .unwrap_or_default()
+        };
+        match extension.as_str(){
+            "json"         => Format::Json,
+            "yaml" | "yml" => Format::Yaml,
+            "env"          => Format::Env,
+            _              => Format::Toml
+        }
+    }
+
+    /// Serialize the Config in this format
+    pub fn serialize(self, config: &Config) -> ConfigResult<String>{
+        match self{
+            Format::Toml => config.serialize(),
+            Format::Json => serde_json::to_string_pretty(config).map(|s| s + "\n")
+                                                                 .map_err(|err| ConfigError::Encode(err.to_string())),
+            Format::Yaml => serde_yaml::to_string(config).map(|s| s + "\n")
+                                                          .map_err(|err| ConfigError::Encode(err.to_string())),
+            Format::Env  => {
+                let mut pairs = Vec::new();
+                flatten(&Value::try_from(config)?, "", &mut pairs);
+                Ok(pairs.into_iter()
+                        .map(|(key, value)| format!("{}={}\n", overrides::key_to_env(key.as_str()), value))
+                        .collect())
+            }
+        }
+    }
+
+    /// Deserialize and migrate a Config in this format. `origin` is used in
+    /// error messages
+    pub fn parse<S>(self, origin: S, contents: &str) -> ConfigResult<(Config, MigrationReport)> where S: Into<String>{
+        let origin = origin.into();
+        let decode = |reason: String| ConfigError::Decode(origin.clone(), reason);
+        match self{
+            Format::Toml => migration::parse(origin.as_str(), contents),
+            Format::Json => {
+                let document: Table = serde_json::from_str(contents).map_err(|err| decode(err.to_string()))?;
+                migration::parse_table(origin.as_str(), document)
+            },
+            Format::Yaml => {
+                let document: Table = serde_yaml::from_str(contents).map_err(|err| decode(err.to_string()))?;
+                migration::parse_table(origin.as_str(), document)
+            },
+            Format::Env => {
+                let defaults = Value::try_from(Config::default())?;
+                let mut pairs = Vec::new();
+                for (number, line) in contents.lines().enumerate(){
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#'){
+                        continue;
+                    }
+                    let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+                    let (name, raw) = match line.find('='){
+                        Some(i) => (line[..i].trim(), line[i+1..].trim()),
+                        None => return Err(decode(format!("line {}: expected NAME=value", number+1)))
+                    };
+                    let value = unquote(raw).map_err(|reason| decode(format!("line {}: {}", number+1, reason)))?;
+                    // Other variables may live in the same file
+                    if let Some(key) = overrides::override_key(&defaults, name){
+                        pairs.push((key, value));
+                    }
+                }
+                // Values that are not in the file keep their defaults
+                let mut config = overrides::apply(&Config::default(), pairs)?;
+                if config.version > CURRENT_VERSION{
+                    return Err(ConfigError::UnsupportedVersion(config.version));
+                }
+                let report = MigrationReport{ from: config.version, to: CURRENT_VERSION, applied: Vec::new() };
+                config.version = CURRENT_VERSION;
+                Ok((config, report))
+            }
+        }
+    }
+}
+
+
+impl FromStr for Format{
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>{
+        match s.trim().to_lowercase().as_str(){
+            "toml"           => Ok(Format::Toml),
+            "json"           => Ok(Format::Json),
+            "yaml" | "yml"   => Ok(Format::Yaml),
+            "env" | "dotenv" => Ok(Format::Env),
+            _ => Err(ConfigError::InvalidValue("format".to_string(), s.to_string(), "expected toml, json, yaml or env".to_string()))
+        }
+    }
+}
+
+impl fmt::Display for Format{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            Format::Toml => write!(f, "toml"),
+            Format::Json => write!(f, "json"),
+            Format::Yaml => write!(f, "yaml"),
+            Format::Env  => write!(f, "env")
+        }
+    }
+}
+
+
+/// Turn a toml Value into `(dotted key, env value)` pairs. Strings are quoted,
+/// so the file can be sourced by a shell
+fn flatten(value: &Value, prefix: &str, out: &mut Vec<(String, String)>){
+    match value{
+        Value::Table(table) => {
+            for (k, v) in table{
+                let key = if prefix.is_empty(){ k.clone() }else{ format!("{}.{}", prefix, k) };
+                flatten(v, key.as_str(), out);
+            }
+        },
+        Value::String(s) => out.push((prefix.to_string(), quote(s))),
+        other => out.push((prefix.to_string(), other.to_string()))
+    }
+}
+
+
+/// Double quote a value, escaping everything a shell would expand
+fn quote(s: &str) -> String{
+    let escaped: String = s.chars()
+                           .map(|c| match c{
+                               '"' | '\\' | '$' | '`' => format!("\\{}", c),
+                               '\n' => "\\n".to_string(),
+                               c => c.to_string()
+                           })
+                           .collect();
+    format!("\"{}\"", escaped)
+}
+
+
+/// Read a value that is either unquoted, single quoted (taken literally) or
+/// double quoted (with backslash escapes)
+fn unquote(raw: &str) -> Result<String, String>{
+    let quote_char = match raw.chars().next(){
+        Some(c) if c == '"' || c == '\'' => c,
+        _ => return Ok(raw.to_string())
+    };
+    if raw.len() < 2 || !raw.ends_with(quote_char){
+        return Err(format!("missing closing {}", quote_char));
+    }
+    let inner = &raw[1..raw.len()-1];
+    if quote_char == '\''{
+        return Ok(inner.to_string());
+    }
+    let mut value = Vec::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next(){
+        if c != '\\'{
+            value.push(c);
+            continue;
+        }
+        match chars.next(){
+            Some('n') => value.push('\n'),
+            Some(escaped) => value.push(escaped),
+            None => return Err("trailing backslash".to_string())
+        }
+    }
+    Ok(value.into_iter().collect())
+}
+
+
+impl Config{
+    /// Serialize the Config to a string in the given format
+    pub fn serialize_as(&self, format: Format) -> ConfigResult<String>{
+        format.serialize(self)
+    }
+
+    /// Deserialize a Config from a string in the given format. Documents
+    /// written by older versions are migrated first
+    pub fn deserialize_as<S>(string: S, format: Format) -> ConfigResult<Self> where S: Into<String>{
+        let string = string.into();
+        let (config, _) = format.parse(format.to_string(), string.as_str())?;
+        Ok(config)
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use format::*;
+
+    #[test]
+    fn roundtrip_all_formats() {
+        let mut c = Config{ servername: "say \"hi\" to $USER\\`whoami`".to_string(), ..Config::default() };
+        c.worker.workload = 4;
+        for format in &[Format::Toml, Format::Json, Format::Yaml, Format::Env]{
+            let serialized = c.serialize_as(*format).unwrap();
+            assert_eq!(Config::deserialize_as(serialized, *format).unwrap(), c, "{} roundtrip", format);
+        }
+        let env = c.serialize_as(Format::Env).unwrap();
+        assert!(env.contains("BENDER_WORKER__WORKLOAD=4\n"));
+        assert!(env.contains("BENDER_SERVERNAME=\"say \\\"hi\\\" to \\$USER\\\\\\`whoami\\`\"\n"));
+    }
+
+    #[test]
+    fn detect_and_parse() {
+        assert_eq!(Format::from_path("/etc/bender/config.toml"), Format::Toml);
+        assert_eq!(Format::from_path("config.JSON"), Format::Json);
+        assert_eq!(Format::from_path("config.yml"), Format::Yaml);
+        assert_eq!(Format::from_path("/run/bender/.env"), Format::Env);
+        assert_eq!(Format::from_path("/etc/bender/config"), Format::Toml);
+        assert_eq!("dotenv".parse::<Format>().unwrap(), Format::Env);
+        assert!("xml".parse::<Format>().is_err());
+
+        // Legacy documents get migrated in every structured format
+        let c = Config::deserialize_as(r#"{"worker": {"heart_rate_seconds": 5}}"#, Format::Json).unwrap();
+        assert_eq!(c.worker.heart_rate, HumanDuration::from_secs(5));
+        let c = Config::deserialize_as("# comment\nexport BENDER_WORKER__WORKLOAD='3'\nPATH=/usr/bin\nBENDER_HOME=/opt\n", Format::Env).unwrap();
+        assert_eq!(c.worker.workload, 3);
+        match Config::deserialize_as("BENDER_SERVERNAME=\"unterminated\n", Format::Env){
+            Err(ConfigError::Decode(_, reason)) => assert!(reason.starts_with("line 1")),
+            other => panic!("Expected a Decode error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn files_by_extension() {
+        let mut dir = std::env::temp_dir();
+        dir.push("bender_config_formats");
+        let dir = dir.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(dir.as_str()).unwrap();
+        let c = Config::default();
+        for name in &["config.json", "config.yaml", "bender.env"]{
+            let path = dir.push(*name);
+            c.to_file(path.as_str()).unwrap();
+            assert_eq!(Config::from_file(path.as_str()).unwrap(), c);
+        }
+        let json = fs::read_to_string(dir.push("config.json")).unwrap();
+        assert!(json.starts_with("{\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
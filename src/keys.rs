@@ -0,0 +1,193 @@
+//! Access to single config values by their dotted key, e.g.
+//! `worker.workload` or `janitor.checking_period`. This is meant
+//! for shell scripts and other services that only need one value and for the
+//! `get`/`set` commands of bender-config.
+use ::*;
+use std::fmt;
+use toml::Value;
+
+
+/// A single value of the Config
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue{
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool)
+}
+
+impl ConfigValue{
+    /// Return the value as str, if it is a String
+    pub fn as_str(&self) -> Option<&str>{
+        match self{
+            ConfigValue::String(s) => Some(s.as_str()),
+            _ => None
+        }
+    }
+
+    /// Return the value as i64, if it is a Integer
+    pub fn as_integer(&self) -> Option<i64>{
+        match self{
+            ConfigValue::Integer(i) => Some(*i),
+            _ => None
+        }
+    }
+
+    /// Return the value as f64, if it is a Float
+    pub fn as_float(&self) -> Option<f64>{
+        match self{
+            ConfigValue::Float(f) => Some(*f),
+            _ => None
+        }
+    }
+
+    /// Return the value as bool, if it is a Boolean
+    pub fn as_bool(&self) -> Option<bool>{
+        match self{
+            ConfigValue::Boolean(b) => Some(*b),
+            _ => None
+        }
+    }
+}
+
+/// Strings are displayed without quotes, so the output can be used in scripts
+impl fmt::Display for ConfigValue{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ConfigValue::String(s)  => write!(f, "{}", s),
+            ConfigValue::Integer(i) => write!(f, "{}", i),
+            ConfigValue::Float(x)   => write!(f, "{}", x),
+            ConfigValue::Boolean(b) => write!(f, "{}", b)
+        }
+    }
+}
+
+
+/// Set the value at the dotted `key` in a toml Value representation of the
+/// Config. The string is parsed into the type of the existing value
+pub fn set_value(root: &mut Value, key: &str, raw: &str) -> ConfigResult<()>{
+    let mut current = root;
+    for part in key.split('.'){
+        current = match current.get_mut(part){
+            Some(v) => v,
+            None => return Err(ConfigError::UnknownKey(key.to_string()))
+        };
+    }
+    let invalid = |reason: String| ConfigError::InvalidValue(key.to_string(), raw.to_string(), reason);
+    let new = match current{
+        Value::String(_)   => Value::String(raw.to_string()),
+        Value::Integer(_)  => Value::Integer(raw.trim().parse().map_err(|_| invalid("expected a integer".to_string()))?),
+        Value::Float(_)    => Value::Float(raw.trim().parse().map_err(|_| invalid("expected a float".to_string()))?),
+        Value::Boolean(_)  => Value::Boolean(raw.trim().parse().map_err(|_| invalid("expected true or false".to_string()))?),
+        other              => return Err(invalid(format!("can't set a {} from a string", other.type_str())))
+    };
+    *current = new;
+    Ok(())
+}
+
+
+/// Like `set_value()`, but deserializes the result again, so values that don't
+/// fit the type of the field (e.g. a negative number for a usize) are rejected
+pub fn set_checked(root: &mut Value, key: &str, raw: &str) -> ConfigResult<()>{
+    set_value(root, key, raw)?;
+    let checked: Result<Config, _> = root.clone().try_into();
+    match checked{
+        Ok(_) => Ok(()),
+        Err(err) => Err(ConfigError::InvalidValue(key.to_string(), raw.to_string(), err.to_string()))
+    }
+}
+
+
+/// Return the dotted keys of all values below `value`
+fn collect_keys(value: &Value, prefix: &str, keys: &mut Vec<String>){
+    match value.as_table(){
+        Some(table) => {
+            for (k, v) in table{
+                let key = if prefix.is_empty(){ k.clone() }else{ format!("{}.{}", prefix, k) };
+                collect_keys(v, key.as_str(), keys);
+            }
+        },
+        None => keys.push(prefix.to_string())
+    }
+}
+
+
+impl Config{
+    /// Return the value at the dotted key
+    pub fn get_key(&self, key: &str) -> ConfigResult<ConfigValue>{
+        let root = Value::try_from(self)?;
+        let mut current = &root;
+        for part in key.split('.'){
+            current = match current.get(part){
+                Some(v) => v,
+                None => return Err(ConfigError::UnknownKey(key.to_string()))
+            };
+        }
+        match current{
+            Value::String(s)  => Ok(ConfigValue::String(s.clone())),
+            Value::Integer(i) => Ok(ConfigValue::Integer(*i)),
+            Value::Float(f)   => Ok(ConfigValue::Float(*f)),
+            Value::Boolean(b) => Ok(ConfigValue::Boolean(*b)),
+            // Sections are not values
+            _ => Err(ConfigError::UnknownKey(key.to_string()))
+        }
+    }
+
+    /// Parse the string into the type of the field at the dotted key and set
+    /// it. The Config stays unchanged if this fails
+    pub fn set_key(&mut self, key: &str, value: &str) -> ConfigResult<()>{
+        *self = overrides::apply(self, vec![(key, value)])?;
+        Ok(())
+    }
+
+    /// Return the dotted keys of all values of the Config
+    pub fn keys(&self) -> Vec<String>{
+        let mut keys = Vec::new();
+        if let Ok(root) = Value::try_from(self){
+            collect_keys(&root, "", &mut keys);
+        }
+        keys
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use keys::*;
+
+    #[test]
+    fn get_keys() {
+        let c = Config::default();
+        assert_eq!(c.get_key("worker.workload").unwrap(), ConfigValue::Integer(1));
+        assert_eq!(c.get_key("rabbitmq.url").unwrap().to_string(), "amqp://localhost//");
+        assert!(c.get_key("worker").is_err());
+        assert!(c.get_key("worker.nope").is_err());
+        assert!(c.keys().contains(&"janitor.checking_period".to_string()));
+    }
+
+    #[test]
+    fn set_keys() {
+        let mut c = Config::default();
+        c.set_key("janitor.checking_period", "30s").unwrap();
+        assert_eq!(c.janitor.checking_period, HumanDuration::from_secs(30));
+        c.set_key("servername", "render.example").unwrap();
+        assert_eq!(c.servername, "render.example");
+        match c.set_key("worker.workload", "1.5"){
+            Err(ConfigError::InvalidValue(key, value, _)) => {
+                assert_eq!(key, "worker.workload");
+                assert_eq!(value, "1.5");
+            },
+            other => panic!("Expected a InvalidValue error, got {:?}", other)
+        }
+        match c.set_key("worker.id", "not-a-uuid"){
+            Err(ConfigError::InvalidValue(_, _, _)) => (),
+            other => panic!("Expected a InvalidValue error, got {:?}", other)
+        }
+        assert!(c.set_key("janitor.nope", "1").is_err());
+        assert_eq!(c.janitor.checking_period, HumanDuration::from_secs(30));
+    }
+}
@@ -34,8 +34,11 @@
 
 
 
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate bender_config_derive;
 extern crate toml;
 extern crate rand;
 extern crate blake2;
@@ -44,11 +47,16 @@ extern crate uuid;
 extern crate dialoguer;
 extern crate console;
 extern crate colored;
+extern crate url;
+extern crate toml_edit;
+extern crate notify;
+#[macro_use]
+extern crate serde_json;
+extern crate serde_yaml;
 
 use rand::prelude::*;
 use rand::distributions::{Alphanumeric};
 
-use std::process::Command;
 use std::fs;
 use std::io::prelude::*;
 use std::path::PathBuf;
@@ -63,32 +71,53 @@ use std::os::unix::fs::DirBuilderExt;
 
 
 pub mod wizard;
-use wizard::{Dialog, print_sectionlabel, print_block};
-
-
+pub mod error;
+pub mod discovery;
+pub mod keys;
+pub mod overrides;
+pub mod builder;
+pub mod validation;
+pub mod migration;
+pub mod edit;
+pub mod atomic;
+pub mod backup;
+pub mod diff;
+pub mod answers;
+pub mod machine_id;
+pub mod watch;
+pub mod units;
+pub mod schema;
+pub mod format;
+pub mod flask;
+pub mod secret;
+pub mod permissions;
+use wizard::{Dialog, Prompter};
+pub use error::{ConfigError, ConfigResult};
+pub use discovery::{discover, ConfigSource, Discovered};
+pub use builder::{ConfigBuilder, Layer, Origins};
+pub use keys::ConfigValue;
+pub use validation::{Issue, Severity};
+pub use migration::{MigrationReport, CURRENT_VERSION};
+pub use diff::Change;
+pub use answers::{Answers, AnswerSource, AnswersPrompter, Prompt};
+pub use watch::{ConfigWatcher, ReloadEvent};
+pub use units::{HumanDuration, ByteSize};
+pub use format::Format;
+pub use secret::AppSecrets;
+
+
+/// Kept for services that still box their errors, bender_config itself only
+/// returns `ConfigError`
 pub type GenError = Box<dyn std::error::Error>;
 pub type GenResult<T> = Result<T, GenError>;
 
 
 
-/// Return the path of the configuration by running `bender-cli config path`
-pub fn path() -> GenResult<String>{
-    let out = Command::new("bender-cli")
-                       .arg("config")
-                       .arg("path")
-                       .output()?;
-    let out = String::from_utf8_lossy(&out.stdout).to_string();
-    let out = out.trim().to_string();
-    if !out.contains("Error"){
-        if std::path::PathBuf::from(out.clone()).exists(){
-            Ok(out)
-        }else{
-            let errmsg = format!("config.toml doesn't exist at path {}", out);
-            Err(From::from(errmsg))
-        }
-    }else{
-        Ok(out)
-    }
+/// Return the path of the configuration. See the `discovery` module for the
+/// locations that are searched
+pub fn path() -> ConfigResult<String>{
+    let discovered = discover(None)?;
+    Ok(discovered.path)
 }
 
 
@@ -99,7 +128,9 @@ pub fn path() -> GenResult<String>{
 #[serde(default)]
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Config{
+    pub version: u32,
     pub servername: String,
+    pub backup_count: usize,
     pub paths: Paths,
     pub flaskbender: Flaskbender,
     pub rabbitmq: RabbitMQ,
@@ -112,7 +143,9 @@ pub struct Config{
 impl Default for Config {
     fn default() -> Self { 
         Self{
+            version: CURRENT_VERSION,
             servername: "bender.render".to_string(),
+            backup_count: 5,
             paths: Paths::default(),
             flaskbender: Flaskbender::default(),
             rabbitmq: RabbitMQ::default(),
@@ -124,95 +157,114 @@ impl Default for Config {
 
 
 impl Config{
-    /// Deserialize a Config from a string of text
-    pub fn deserialize<S>(string: S) -> GenResult<Self> where S: Into<String>{
+    /// Deserialize a Config from a string of text. Documents written by older
+    /// versions are migrated first
+    pub fn deserialize<S>(string: S) -> ConfigResult<Self> where S: Into<String>{
         let string = string.into();
-        let config: Self = toml::from_str(string.as_str())?;
+        let (config, _) = migration::parse("string", string.as_str())?;
         Ok(config)
     }
 
     /// Deserialize a Config from a slice of bytes
-    pub fn deserialize_from_u8(v: &[u8]) -> GenResult<Self>{
-        let config: Self = toml::from_slice(v)?;
+    pub fn deserialize_from_u8(v: &[u8]) -> ConfigResult<Self>{
+        let string = String::from_utf8_lossy(v);
+        let (config, _) = migration::parse("bytes", &string)?;
         Ok(config)
     }
 
     /// Serialize the Config to a pretty string
-    pub fn serialize(&self) -> GenResult<String>{
+    pub fn serialize(&self) -> ConfigResult<String>{
         let serialized: String = toml::to_string_pretty(self)?;
         Ok(serialized)
     }
 
     /// Serialize the Config to a vector of bytes
-    pub fn serialize_to_u8(&self) -> GenResult<Vec<u8>>{
+    pub fn serialize_to_u8(&self) -> ConfigResult<Vec<u8>>{
         let serialized: Vec<u8> = toml::to_vec(self)?;
         Ok(serialized)
     }
 
-    /// Deserialize the Config from a file
-    pub fn from_file<S>(path: S) -> GenResult<Self> where S: Into<String>{
-        let path = path.into();
-        let mut file = fs::File::open(path.trim())?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        let deserialized = Self::deserialize(contents.as_str())?;
+    /// Deserialize the Config from a file. The format is detected by the
+    /// extension (see `Format::from_path()`)
+    pub fn from_file<S>(path: S) -> ConfigResult<Self> where S: Into<String>{
+        let (deserialized, _) = Self::from_file_migrated(path)?;
         Ok(deserialized)
     }
 
-    /// Serialize the Config to a file
-    pub fn to_file<S>(&self, path:S) -> GenResult<()> where S: Into<String>{
+    /// Deserialize the Config from a file and report which migrations had to
+    /// be applied. If `MigrationReport::migrated()` is true the file should be
+    /// rewritten
+    pub fn from_file_migrated<S>(path: S) -> ConfigResult<(Self, MigrationReport)> where S: Into<String>{
         let path = path.into();
-        let mut file = fs::File::create(path.as_str())?;
-        let serialized = self.serialize_to_u8()?;
-        file.write_all(&serialized)?;
-        Ok(())
+        let path = path.trim();
+        let contents = fs::read_to_string(path)
+                           .map_err(|err| ConfigError::from_io(path, err))?;
+        Format::from_path(path).parse(path, contents.as_str())
     }
 
-    /// Serialize the Config to the location specified in `self.paths.config`
-    pub fn write_changes(&self) -> GenResult<()>{
-        self.to_file(self.paths.config.clone())?;
+    /// Serialize the Config to a file in the format matching its extension.
+    /// The file is replaced atomically
+    pub fn to_file<S>(&self, path:S) -> ConfigResult<()> where S: Into<String>{
+        let path = path.into();
+        let serialized = match Format::from_path(path.as_str()){
+            Format::Toml => self.serialize_to_u8()?,
+            format => self.serialize_as(format)?.into_bytes()
+        };
+        atomic::write_atomic(path, &serialized)
+    }
+
+    /// Serialize the Config to the location specified in `self.paths.config`.
+    /// Comments and formatting of a existing file are preserved. The previous
+    /// file is kept as a backup (see the `backup` module)
+    pub fn write_changes(&self) -> ConfigResult<()>{
+        backup::create_backup(self.paths.config.as_str(), self.backup_count)?;
+        self.update_file(self.paths.config.clone())?;
         Ok(())
     }
 
     /// Update the Config from the location specified in `self.paths.config`
-    pub fn read_changes(&mut self) -> GenResult<()>{
-        let mut file = fs::File::open(self.paths.config.as_str())?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        let deserialized = Self::deserialize(contents.as_str())?;
+    pub fn read_changes(&mut self) -> ConfigResult<()>{
+        let deserialized = Self::from_file(self.paths.config.as_str())?;
         *self = deserialized;
         Ok(())
     }
 
     /// The goto method to get a config file. This is what other services should
-    /// use. This relies on `bender-cli config path` to get the config path and
-    /// fails horribly if no config is present or no bender-cli is in PATH
-    pub fn get() -> Self{
-        let configpath = match path(){
-            Ok(c)     =>  c,
-            Err(_err) =>  {
-                eprintln!("Error: Didn't find a server configuration. Install bender-cli and run bender-cli setup! {}", _err);
-                std::process::exit(1);
-            }
-        };
+    /// use. The config is searched for as described in the `discovery` module,
+    /// a `ConfigError` describes which step failed
+    pub fn try_get() -> ConfigResult<Self>{
+        let (config, _) = Self::try_get_from(None)?;
+        Ok(config)
+    }
 
-        // Check if bender-cli config path returned an error
-        if configpath.contains("There is no config.toml at"){
-            eprintln!("Error: There is no config.toml, use bender-cli to generate one");
-            std::process::exit(1);
+    /// Like `Config::try_get()`, but starts the discovery with a explicit path
+    /// (if given) and also returns where the config was found. Overrides from
+    /// `BENDER_*` environment variables are applied to the result, which is
+    /// then validated. Validation warnings are printed to stderr
+    pub fn try_get_from(explicit: Option<&str>) -> ConfigResult<(Self, Discovered)>{
+        let discovered = discover(explicit)?;
+        let (mut config, report) = Self::from_file_migrated(discovered.path.as_str())?;
+        if report.migrated(){
+            eprintln!("Warning: {} was written for config version {}, run the wizard to update it to version {}", discovered.path, report.from, report.to);
         }
-
-        // Double check if the thing is really there
-        if !std::path::PathBuf::from(configpath.clone()).exists(){
-            eprintln!("Error: There is no config.toml, use bender-cli to generate one");
-            std::process::exit(1);
+        config.apply_env()?;
+        for warning in config.ensure_valid()?{
+            eprintln!("{}", warning);
         }
+        Ok((config, discovered))
+    }
 
-        // Finally try to deserialize the dame thing
-        match Config::from_file(configpath){
+    /// Like `Config::try_get()`, but prints the error and exits the process
+    /// if no config could be loaded. Only use this in short lived CLI tools
+    pub fn get() -> Self{
+        match Config::try_get(){
             Ok(config) => config,
-            Err(err)   =>{
-                eprintln!("Error: Error while deserializing the configuration: {}", err);
+            Err(err)   => {
+                eprintln!("Error: {}", err);
+                match err{
+                    ConfigError::Parse(_, _) | ConfigError::Invalid(_) => (),
+                    _ => eprintln!("Run bender-cli setup to generate a config.toml or point {} to one", discovery::ENV_VAR)
+                }
                 std::process::exit(1);
             }
         }
@@ -220,52 +272,66 @@ impl Config{
 }
 
 
+const SERVERNAME_PROMPT: &str = "The name of the server (displayed in the header of the website)";
+
 impl Dialog for Config{
-    fn ask() -> Self{
-        let servername = Input::<String>::new()
-                                        .with_prompt("The name of the server (displayed in the header of the website)")
-                                        .default("bender.render".to_string())
-                                        .interact()
-                                        .expect("Couldn't display dialog.");
+    fn ask(prompter: &mut dyn Prompter) -> Self{
+        let servername = wizard::input(prompter, "servername", SERVERNAME_PROMPT, "bender.render".to_string());
         
         Self{
+            version: CURRENT_VERSION,
             servername,
-            paths: Paths::ask(),
-            flaskbender: Flaskbender::ask(),
-            rabbitmq: RabbitMQ::ask(),
-            janitor: Janitor::ask(),
-            worker: Worker::ask()
+            backup_count: Config::default().backup_count,
+            paths: Paths::ask(prompter),
+            flaskbender: Flaskbender::ask(prompter),
+            rabbitmq: RabbitMQ::ask(prompter),
+            janitor: Janitor::ask(prompter),
+            worker: Worker::ask(prompter)
         }
     }
 
-    fn compare(&self, other: Option<&Self>) -> Self{
+    fn compare(&self, prompter: &mut dyn Prompter, other: Option<&Self>) -> Self{
         match other{
             Some(o) => {
-                print_block(" The server name (shows up in frontend) ");
-                let servername = wizard::differ(self.servername.clone(), Some(o.servername.clone()));
+                prompter.print_block(" The server name (shows up in frontend) ");
+                let servername = wizard::differ(prompter, self.servername.clone(), Some(o.servername.clone()));
                 Self{
+                    version: CURRENT_VERSION,
                     servername,
-                    paths: self.paths.compare(Some(&o.paths)),
-                    flaskbender: self.flaskbender.compare(Some(&o.flaskbender)),
-                    rabbitmq: self.rabbitmq.compare(Some(&o.rabbitmq)),
-                    janitor: self.janitor.compare(Some(&o.janitor)),
-                    worker: self.worker.compare(Some(&o.worker))
+                    backup_count: self.backup_count,
+                    paths: self.paths.compare(prompter, Some(&o.paths)),
+                    flaskbender: self.flaskbender.compare(prompter, Some(&o.flaskbender)),
+                    rabbitmq: self.rabbitmq.compare(prompter, Some(&o.rabbitmq)),
+                    janitor: self.janitor.compare(prompter, Some(&o.janitor)),
+                    worker: self.worker.compare(prompter, Some(&o.worker))
                 }
             },
             None => {
-                print_block(" The server name (shows up in frontend) ");
-                let servername = wizard::differ(self.servername.clone(), None);
+                prompter.print_block(" The server name (shows up in frontend) ");
+                let servername = wizard::differ(prompter, self.servername.clone(), None);
                 Self{
+                    version: CURRENT_VERSION,
                     servername,
-                    paths: self.paths.compare(None),
-                    flaskbender: self.flaskbender.compare(None),
-                    rabbitmq: self.rabbitmq.compare(None),
-                    janitor: self.janitor.compare(None),
-                    worker: self.worker.compare(None)
+                    backup_count: self.backup_count,
+                    paths: self.paths.compare(prompter, None),
+                    flaskbender: self.flaskbender.compare(prompter, None),
+                    rabbitmq: self.rabbitmq.compare(prompter, None),
+                    janitor: self.janitor.compare(prompter, None),
+                    worker: self.worker.compare(prompter, None)
                 }
             }
         }
     }
+
+    fn descriptions() -> Vec<(&'static str, &'static str)>{
+        vec![("", "The config of the bender renderfarm, shared by all of its services"),
+             ("version", "The version of the config format, older files are migrated when read"),
+             ("servername", SERVERNAME_PROMPT),
+             ("backup_count", "How many backups of the config.toml are kept when it is changed"),
+             ("paths", "Where the config, the app.secret and the uploaded files are stored"),
+             ("flaskbender", "The web frontend that accepts the uploads"),
+             ("rabbitmq", "The message queue the services talk over")]
+    }
 }
 
 
@@ -289,20 +355,20 @@ impl Config {
     }
 
     /// Reads the appsecret from its path
-    pub fn read_appsecret(&self) -> GenResult<String>{
-        let mut file = fs::File::open(self.get_appsecret_path().as_str())?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+    pub fn read_appsecret(&self) -> ConfigResult<String>{
+        let path = self.get_appsecret_path();
+        let contents = fs::read_to_string(path.as_str())
+                           .map_err(|err| ConfigError::from_io(path.as_str(), err))?;
         Ok(contents)
     }
 
-    /// Writes the appsecret to its path
-    pub fn write_appsecret(&self) -> GenResult<()>{
-        let mut file = fs::File::create(self.get_appsecret_path().as_str())?;
+    /// Writes the appsecret to its path. The file is replaced atomically and
+    /// only readable by its owner, the private directory is created if it
+    /// doesn't exist. This invalidates everything signed with the old secret immediately,
+    /// use `rotate_appsecret()` to keep the old one around for a while
+    pub fn write_appsecret(&self) -> ConfigResult<()>{
         let appsecret = Self::generate_appsecret();
-        let appsecret = appsecret.as_bytes();
-        file.write_all(&appsecret)?;
-        Ok(())
+        self.write_secret(self.get_appsecret_path(), appsecret.as_bytes())
     }
 
     /// Gets the appsecret path (basically push app.secret to the private path)
@@ -321,7 +387,7 @@ impl Config {
 
     /// Return a salt to be use for private fields. The salt is a blake2 hashed
     /// version of the appsecret
-    pub fn get_salt(&self) -> GenResult<String>{
+    pub fn get_salt(&self) -> ConfigResult<String>{
         // Try to read the appsecret
         match self.read_appsecret(){
             Ok(appsecret) => {
@@ -342,10 +408,19 @@ impl Config {
 
 // ============================== PATHS STRUCT ===============================
 #[serde(default)]
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Dialog)]
 pub struct Paths{
+    #[dialog(skip, text = "The location of the config.toml itself")]
     pub config: Path,
+    #[dialog(prompt = "Specify the directory where the app.secret for flaskbender should be stored",
+             block = "\n config.paths.private (where the app.secret is stored) ")]
     pub private: Path,
+    #[dialog(skip, text = "The user (name or uid) owning the private directory and the app.secret, empty for the user writing them")]
+    pub private_owner: String,
+    #[dialog(skip, text = "The group (name or gid) that may enter the private directory, empty for none")]
+    pub private_group: String,
+    #[dialog(prompt = "Specify the directory where the uploaded blendfiles and the rendered frames will be stored",
+             block = "\n config.paths.upload (where the both the uploaded blendfiles and the rendered frames are stored) ")]
     pub upload: Path
 }
 
@@ -367,6 +442,8 @@ impl Default for Paths{
         Self{
             config: "/etc/bender/config.toml".to_string(),
             private: "/var/lib/flask/private".to_string(),
+            private_owner: String::new(),
+            private_group: String::new(),
             upload: "/data/bender".to_string()
         }
     }
@@ -376,15 +453,17 @@ impl Default for Paths{
 type Path = String;
 
 pub trait PathMethods{
-    fn is_writeable(&self) -> GenResult<bool>;
+    fn is_writeable(&self) -> ConfigResult<bool>;
     fn exists(&self) -> bool;
     fn push<S>(&self, s: S) -> String where S: Into<String>;
 }
 
 impl PathMethods for Path{
     /// Returns Ok(true) if the path is writeable and returns Ok(false) if not.
-    /// For every other reason a write could have failed return a Error
-    fn is_writeable(&self) -> GenResult<bool>{
+    /// For every other reason a write could have failed return a Error.
+    /// Missing directories are created shared (mode 2775), so use
+    /// `Config::private_is_writeable()` for the private directory
+    fn is_writeable(&self) -> ConfigResult<bool>{
         let p = PathBuf::from(self.clone());
         // Naive check: if this thing has a dot in it it must be a file
         match p.extension(){
@@ -402,7 +481,8 @@ impl PathMethods for Path{
                     builder.mode(0o2775);
                     
                     builder.recursive(true)
-                           .create(&folder)?;
+                           .create(&folder)
+                           .map_err(|err| ConfigError::from_io(folder.to_string_lossy(), err))?;
                 }
                 let file = fs::OpenOptions::new().append(true)
                                                  .create(true)
@@ -412,7 +492,7 @@ impl PathMethods for Path{
                     Ok(f) => {
                         match f.metadata(){
                             Ok(metadata) => {
-                                if let 0 = metadata.len() { fs::remove_file(p)?}
+                                if let 0 = metadata.len() { fs::remove_file(p).map_err(|err| ConfigError::from_io(self.as_str(), err))?}
                             },
                             Err(err) => eprintln!("Error while retrieving metadata: {}", err)
                         }
@@ -421,7 +501,7 @@ impl PathMethods for Path{
                     Err(err) => match err.kind(){
                         std::io::ErrorKind::PermissionDenied => Ok(false),
                         std::io::ErrorKind::AlreadyExists => Ok(true),
-                        _ => Err(From::from(err))
+                        _ => Err(ConfigError::Io(self.clone(), err))
                     }
                 }
             }
@@ -438,7 +518,7 @@ impl PathMethods for Path{
                     Err(err) => match err.kind(){
                         std::io::ErrorKind::PermissionDenied => Ok(false),
                         std::io::ErrorKind::AlreadyExists => Ok(true),
-                        _ => Err(From::from(err))
+                        _ => Err(ConfigError::Io(self.clone(), err))
                     }
                 }
             }
@@ -462,145 +542,43 @@ impl PathMethods for Path{
 
 
 
-impl Dialog for Paths{
-    fn ask() -> Self{
-        println!();
-        print_sectionlabel("Paths");
-        let config = "/etc/bender/config.toml".to_string();
-
-        let private = Input::<String>::new().with_prompt("Specify the directory where the app.secret for flaskbender should be stored")
-                                           .default("/var/lib/flask/private".to_string())
-                                           .interact()
-                                           .expect("Couldn't display dialog.");
-
-        let upload = Input::<String>::new().with_prompt("Specify the directory where the uploaded blendfiles and the rendered frames will be stored")
-                                           .default("/data/bender".to_string())
-                                           .interact()
-                                           .expect("Couldn't display dialog.");
-        
-        Self{
-            config,
-            private,
-            upload,
-        }
-    }
-
-    fn compare(&self, other: Option<&Self>) -> Self{
-        println!();
-        print_sectionlabel("Paths");
-        match other{
-            Some(o) => {
-                let config = "/etc/bender/config.toml".to_string();
-                print_block("\n config.paths.private (where the app.secret is stored) ");
-                let private = wizard::differ(self.private.clone(), Some(o.private.clone()));
-                print_block("\n config.paths.upload (where the both the uploaded blendfiles and the rendered frames are stored) ");
-                let upload = wizard::differ(self.upload.clone(), Some(o.upload.clone()));
-                Self{
-                    config,
-                    private,
-                    upload,
-                }
-            },
-            None => {
-                let config = "/etc/bender/config.toml".to_string();
-                print_block("\n config.paths.private (where the app.secret is stored) ");
-                let private = wizard::differ(self.private.clone(), None);
-                print_block("\n config.paths.upload (where the both the uploaded blendfiles and the rendered frames are stored) ");
-                let upload = wizard::differ(self.upload.clone(), None);
-                Self{
-                    config,
-                    private,
-                    upload,
-                }
-            }
-        }
-    }
-}
-
-
-
-
-
 // ========================== FLASKBENDER STRUCT =============================
 #[serde(default)]
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Dialog)]
 pub struct Flaskbender{
-    pub upload_limit: usize,
+    #[dialog(prompt = "The maximum upload size (e.g. 500MB, 2GB)",
+             block = "\n The upload limit (max file size) ")]
+    pub upload_limit: ByteSize,
+    #[dialog(skip, text = "The URL blendfiles are uploaded to")]
     pub upload_url: String,
-    pub job_cookie_name: String
+    #[dialog(prompt = "The name of the secure cookie, where the users job IDs are stored",
+             block = "\n The name of the secure cookie in which the client stores it's job ids ")]
+    pub job_cookie_name: String,
+    #[dialog(prompt = "How long should the previous app.secret stay valid after it was rotated? (e.g. 7d)",
+             block = "\n How long the previous app.secret stays valid after a rotation ")]
+    pub secret_overlap: HumanDuration
 }
 
 
 impl Default for Flaskbender{
     fn default() -> Self{ 
         Self{
-            upload_limit: 2,
+            upload_limit: ByteSize::from_gb(2),
             upload_url: "http://localhost:5000/blendfiles/".to_string(),
             job_cookie_name: "bender-renderjobs".to_string(),
-        }
-    }
-}
-
-impl Dialog for Flaskbender{
-    fn ask() -> Self{
-        println!();
-        print_sectionlabel("Flaskbender");
-        let upload_limit = Input::<usize>::new().with_prompt("The maximum upload size in GB")
-                                                .default(2)
-                                                .interact()
-                                                .expect("Couldn't display dialog.");
-        // let upload_url = Input::<usize>::new().with_prompt("The upload URL").default("http://localhost:5000/blendfiles/".to_string()).interact().expect("Couldn't display dialog.");
-        let job_cookie_name = Input::<String>::new().with_prompt("The name of the secure cookie, where the users job IDs are stored")
-                                                    .default("bender-renderjobs".to_string())
-                                                    .interact()
-                                                    .expect("Couldn't display dialog.");
-        
-        Self{
-            upload_limit,
-            upload_url: "http://localhost:5000/blendfiles/".to_string(),
-            job_cookie_name,
-        }
-    }
-
-    fn compare(&self, other: Option<&Self>) -> Self{
-        println!();
-        print_sectionlabel("Flaskbender");
-        match other{
-            Some(o) => {
-                print_block("\n The upload limit (max file size) in GB ");
-                let upload_limit = wizard::differ(self.upload_limit, Some(o.upload_limit));
-                // let upload_url = wizard::differ(self.upload_url.clone(), Some(o.upload_url.clone()));
-                print_block("\n The name of the secure cookie in which the client stores it's job ids ");
-                let job_cookie_name = wizard::differ(self.job_cookie_name.clone(), Some(o.job_cookie_name.clone()));
-                Self{
-                    upload_limit,
-                    upload_url: "http://localhost:5000/blendfiles/".to_string(),
-                    job_cookie_name,
-                }
-            },
-            None => {
-                print_block("\n The upload limit (max file size) in GB ");
-                let upload_limit = wizard::differ(self.upload_limit, None);
-                // let upload_url = wizard::differ(self.upload_url.clone(), Some(o.upload_url.clone()));
-                print_block("\n The name of the secure cookie in which the client stores it's job ids ");
-                let job_cookie_name = wizard::differ(self.job_cookie_name.clone(), None);
-                Self{
-                    upload_limit,
-                    upload_url: "http://localhost:5000/blendfiles/".to_string(),
-                    job_cookie_name,
-                }
-            }
+            secret_overlap: HumanDuration::from_mins(60*24*7)
         }
     }
 }
 
 
 
-
 // ============================ RABBITMQ STRUCT ==============================
 #[serde(default)]
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Dialog)]
 pub struct RabbitMQ{
+    #[dialog(prompt = "RabbitMQ URL",
+             block = "\n The AMQP URL for e.g. RabbitMQ ")]
     pub url: String
 }
 
@@ -613,255 +591,91 @@ impl Default for RabbitMQ{
     }
 }
 
-impl Dialog for RabbitMQ{
-    fn ask() -> Self{
-        println!();
-        print_sectionlabel("RabbitMQ");
-        let url = Input::<String>::new().with_prompt("RabbitMQ URL").default( "amqp://localhost//".to_string()).interact().expect("Couldn't display dialog.");
-        
-        Self{
-            url
-        }
-    }
-
-    fn compare(&self, other: Option<&Self>) -> Self{
-        println!();
-        print_sectionlabel("RabbitMQ");
-        match other{
-            Some(o) => {
-                print_block("\n The AMQP URL for e.g. RabbitMQ ");
-                let url = wizard::differ(self.url.clone(), Some(o.url.clone()));
-                Self{
-                    url
-                }
-            },
-            None => {
-                print_block("\n The AMQP URL for e.g. RabbitMQ ");
-                let url = wizard::differ(self.url.clone(), None);
-                Self{
-                    url
-                }
-            }
-        }
-    }
-}
-
 
 
 // =========================== JANITOR STRUCT ==============================
 #[serde(default)]
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Dialog)]
+#[dialog(section = "bender-janitor",
+         text = "The bender-janitor service cleans up jobs and job files that have somehow ended (e.g. canceled, errored, finished etc)")]
 pub struct Janitor{
-    pub checking_period_seconds:       usize,
-    pub error_deletion_min_minutes:    usize,
-    pub error_deletion_max_minutes:    usize,
-    pub finish_deletion_min_minutes:   usize,
-    pub finish_deletion_max_minutes:   usize,
-    pub cancel_deletion_min_minutes:   usize,
-    pub cancel_deletion_max_minutes:   usize
+    #[dialog(prompt = "How frequenctly should the janitor check for cleaning? (e.g. 90s, 5m)",
+             block = "\n How often should the janitor check for cleanup? ")]
+    pub checking_period:       HumanDuration,
+    #[dialog(text = "\nThe bender-janitor will dynamically decide when to keep a job around for longer (e.g. when there is a lot of free disk space) and when to delete these jobs. You can specify minimum and maximum times (e.g. 12h, 14d or 1d12h):",
+             prompt = "Minimum grace period for deletion after error",
+             block = "\n Minimum: How long to keep jobs after Error? ")]
+    pub error_deletion_min:    HumanDuration,
+    #[dialog(prompt = "Maximum grace period for deletion after error",
+             block = "\n Maximum: How long to keep jobs after Error? ")]
+    pub error_deletion_max:    HumanDuration,
+    #[dialog(prompt = "Minimum grace period for jobs finished, but not downloaded",
+             block = "\n Minimum: How long to keep jobs after finish? ")]
+    pub finish_deletion_min:   HumanDuration,
+    #[dialog(prompt = "Maximum grace period for jobs finished, but not downloaded",
+             block = "\n Maximum: How long to keep jobs after finish? ")]
+    pub finish_deletion_max:   HumanDuration,
+    #[dialog(prompt = "Minimum grace period for canceled jobs",
+             block = "\n Minimum: How long to keep jobs after cancelation? ")]
+    pub cancel_deletion_min:   HumanDuration,
+    #[dialog(prompt = "Maximum grace period for canceled jobs",
+             block = "\n Maximum: How long to keep jobs after cancelation? ")]
+    pub cancel_deletion_max:   HumanDuration
 }
 
 
 impl Default for Janitor{
     fn default() -> Self{ 
         Self{
-            checking_period_seconds: 60,
-            error_deletion_min_minutes: 60*24,
-            error_deletion_max_minutes: 60*24*14,
-            finish_deletion_min_minutes: 60*24,
-            finish_deletion_max_minutes: 60*24*14,
-            cancel_deletion_min_minutes: 15,
-            cancel_deletion_max_minutes: 15
-        }
-    }
-}
-
-impl Dialog for Janitor{
-    fn ask() -> Self{
-        println!();
-        print_sectionlabel("bender-janitor");
-        println!("The bender-janitor service cleans up jobs and job files that have somehow ended (e.g. canceled, errored, finished etc)");
-        let checking_period_seconds = Input::<usize>::new().with_prompt("How frequenctly should the janitor check for cleaning? (in seconds)").default(60).interact().expect("Couldn't display dialog.");
-        
-        println!("\nThe bender-janitor will dynamically decide when to keep a job around for longer (e.g. when there is a lot of free disk space) and when to delete these jobs. You can specify minimum and maximum times:");
-        let error_deletion_min_minutes    = Input::<usize>::new().with_prompt("Minimum grace period for deletion after error (in minutes)").default(60*24).interact().expect("Couldn't display dialog.");
-        let error_deletion_max_minutes    = Input::<usize>::new().with_prompt("Maximum grace period for deletion after error (in minutes)").default(60*24*14).interact().expect("Couldn't display dialog.");
-        let finish_deletion_min_minutes   = Input::<usize>::new().with_prompt("Minimum grace period for jobs finished, but not downloaded (in minutes)").default(60*24).interact().expect("Couldn't display dialog.");
-        let finish_deletion_max_minutes   = Input::<usize>::new().with_prompt("Maximum grace period for jobs finished, but not downloaded (in minutes)").default(60*24*14).interact().expect("Couldn't display dialog.");
-        let cancel_deletion_min_minutes   = Input::<usize>::new().with_prompt("Minimum grace period for canceled jobs (in minutes)").default(15).interact().expect("Couldn't display dialog.");
-        let cancel_deletion_max_minutes   = Input::<usize>::new().with_prompt("Maximum grace period for canceled jobs (in minutes)").default(15).interact().expect("Couldn't display dialog.");
-
-        Self{
-            checking_period_seconds,
-            error_deletion_min_minutes,
-            error_deletion_max_minutes,
-            finish_deletion_min_minutes,
-            finish_deletion_max_minutes,
-            cancel_deletion_min_minutes,
-            cancel_deletion_max_minutes
-        }
-    }
-
-    fn compare(&self, other: Option<&Self>) -> Self{
-        println!();
-        print_sectionlabel("bender-janitor");
-        match other{
-            Some(o) => {
-                print_block("\n How often should the janitor check for cleanup? (in seconds) ");
-                let checking_period_seconds = wizard::differ(self.checking_period_seconds, Some(o.checking_period_seconds));
-
-                print_block("\n Minimum: How long to keep jobs after Error? (in minutes) ");
-                let error_deletion_min_minutes    = wizard::differ(self.error_deletion_min_minutes, Some(o.error_deletion_min_minutes));
-                print_block("\n Maximum: How long to keep jobs after Error? (in minutes) ");
-                let error_deletion_max_minutes    = wizard::differ(self.error_deletion_max_minutes, Some(o.error_deletion_max_minutes));
-                print_block("\n Minimum: How long to keep jobs after finish? (in minutes) ");
-                let finish_deletion_min_minutes   = wizard::differ(self.finish_deletion_min_minutes, Some(o.finish_deletion_min_minutes));
-                print_block("\n Maximum: How long to keep jobs after finish? (in minutes) ");
-                let finish_deletion_max_minutes   = wizard::differ(self.finish_deletion_max_minutes, Some(o.finish_deletion_max_minutes));
-                print_block("\n Minimum: How long to keep jobs after cancelation? (in minutes) ");
-                let cancel_deletion_min_minutes   = wizard::differ(self.cancel_deletion_min_minutes, Some(o.cancel_deletion_min_minutes));
-                print_block("\n Maximum: How long to keep jobs after cancelation? (in minutes) ");
-                let cancel_deletion_max_minutes   = wizard::differ(self.cancel_deletion_max_minutes, Some(o.cancel_deletion_max_minutes));
-                
-                Self{
-                    checking_period_seconds,
-                    error_deletion_min_minutes,
-                    error_deletion_max_minutes,
-                    finish_deletion_min_minutes,
-                    finish_deletion_max_minutes,
-                    cancel_deletion_min_minutes,
-                    cancel_deletion_max_minutes
-                }
-            },
-            None => {
-                print_block("\n How often should the janitor check for cleanup? (in seconds) ");
-                let checking_period_seconds = wizard::differ(self.checking_period_seconds, None);
-
-                print_block("\n Minimum: How long to keep jobs after Error? (in minutes) ");
-                let error_deletion_min_minutes    = wizard::differ(self.error_deletion_min_minutes, None);
-                print_block("\n Maximum: How long to keep jobs after Error? (in minutes) ");
-                let error_deletion_max_minutes    = wizard::differ(self.error_deletion_max_minutes, None);
-                print_block("\n Minimum: How long to keep jobs after finish? (in minutes) ");
-                let finish_deletion_min_minutes   = wizard::differ(self.finish_deletion_min_minutes, None);
-                print_block("\n Maximum: How long to keep jobs after finish? (in minutes) ");
-                let finish_deletion_max_minutes   = wizard::differ(self.finish_deletion_max_minutes, None);
-                print_block("\n Minimum: How long to keep jobs after cancelation? (in minutes) ");
-                let cancel_deletion_min_minutes   = wizard::differ(self.cancel_deletion_min_minutes, None);
-                print_block("\n Maximum: How long to keep jobs after cancelation? (in minutes) ");
-                let cancel_deletion_max_minutes   = wizard::differ(self.cancel_deletion_max_minutes, None);
-                
-                Self{
-                    checking_period_seconds,
-                    error_deletion_min_minutes,
-                    error_deletion_max_minutes,
-                    finish_deletion_min_minutes,
-                    finish_deletion_max_minutes,
-                    cancel_deletion_min_minutes,
-                    cancel_deletion_max_minutes
-                }
-            }
+            checking_period: HumanDuration::from_secs(60),
+            error_deletion_min: HumanDuration::from_mins(60*24),
+            error_deletion_max: HumanDuration::from_mins(60*24*14),
+            finish_deletion_min: HumanDuration::from_mins(60*24),
+            finish_deletion_max: HumanDuration::from_mins(60*24*14),
+            cancel_deletion_min: HumanDuration::from_mins(15),
+            cancel_deletion_max: HumanDuration::from_mins(15)
         }
     }
 }
 
 
 
-
-
 // =========================== WORKER STRUCT ==============================
 #[serde(default)]
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Dialog)]
+#[dialog(section = "bender-worker",
+         text = "The bender-worker is the client that actually executes tasks from the queue. It can run on the server or on a client. This configuration is only relevant for workers running on the server.\n")]
 pub struct Worker{
+    #[dialog(with = "machine_id::dialog", text = "The id of this worker towards bender-qu (also stored in the machine-id file)")]
     pub id: Uuid,
-    pub disklimit: u64,
-    pub grace_period: u64,
+    #[dialog(prompt = "How much disk space should the worker keep free? (e.g. 500MB, 2GB)",
+             block = "\n The Workers disklimit (if exceeded don't accept new jobs) ")]
+    pub disklimit: ByteSize,
+    #[dialog(prompt = "How long should downloaded blendfiles be kept around (ireelevant on server)? (e.g. 60s, 10m)",
+             block = "\n The Workers grace period (how long downloaded blendfiles are kept around - irrelevant for server ")]
+    pub grace_period: HumanDuration,
+    #[dialog(prompt = "How many frames should the worker render at once?",
+             block = "\n How many frames should a worker accept at once? ")]
     pub workload: usize,
-    pub heart_rate_seconds: isize
+    #[dialog(prompt = "How often should the worker send a heartbeat message to bender-qu at max? (e.g. 10s)",
+             block = "\nHow often should the worker send a heartbeat message to bender-qu at max? ")]
+    pub heart_rate: HumanDuration
 }
 
 
 impl Default for Worker{
     fn default() -> Self{ 
         Self{
-            id: Uuid::new_v4(),       // Worker Random ID asigned uppon config
-            disklimit: 2,             // in GB
-            grace_period: 60,         // How many seconds to keep blendfiles,
+            id: machine_id::default_id(), // From the machine-id file if there is one
+            disklimit: ByteSize::from_gb(2), // Disk space to keep free
+            grace_period: HumanDuration::from_secs(60), // How long to keep blendfiles,
             workload: 1,              // How many frames to render at once,
-            heart_rate_seconds: 10    // How often to send out a heart beat
+            heart_rate: HumanDuration::from_secs(10)    // How often to send out a heart beat
         }
     }
 }
 
 
-impl Dialog for Worker{
-    fn ask() -> Self{
-        println!();
-        print_sectionlabel("bender-worker");
-        println!("The bender-worker is the client that actually executes tasks from the queue. It can run on the server or on a client. This configuration is only relevant for workers running on the server.\n");
-        let disklimit = Input::<u64>::new().with_prompt("How much disk space should the worker keep free? (in GB)").default(2).interact().expect("Couldn't display dialog.");
-        let grace_period = Input::<u64>::new().with_prompt("How long should downloaded blendfiles be kept around (ireelevant on server)? (in secs)").default(60).interact().expect("Couldn't display dialog.");
-        let workload = Input::<usize>::new().with_prompt("How many frames should the worker render at once?").default(1).interact().expect("Couldn't display dialog.");
-        let heart_rate_seconds = Input::<isize>::new().with_prompt("How often should the worker send a heartbeat message to bender-qu at max (in seconds)?").default(10).interact().expect("Couldn't display dialog.");
-        
-        Self{
-            id: Uuid::new_v4(),
-            disklimit,
-            grace_period,
-            workload,
-            heart_rate_seconds
-        }
-    }
-
-    fn compare(&self, other: Option<&Self>) -> Self{
-        println!();
-        print_sectionlabel("bender-worker");
-        match other{
-            Some(o) => {
-                print_block("\n The Workers disklimit in GB (if exceeded don't accept new jobs) ");
-                let disklimit = wizard::differ(self.disklimit, Some(o.disklimit));
-                print_block("\n The Workers grace period (how long downloaded blendfiles are kept around in seconds - irrelevant for server ");
-                let grace_period = wizard::differ(self.grace_period, Some(o.grace_period));
-                print_block("\n How many frames should a worker accept at once? ");
-                let workload = wizard::differ(self.workload, Some(o.workload));
-                print_block("\nHow often should the worker send a heartbeat message to bender-qu at max (in seconds)? ");
-                let heart_rate_seconds = wizard::differ(self.heart_rate_seconds, Some(o.heart_rate_seconds));
-
-                Self{
-                    id: Uuid::new_v4(),
-                    disklimit,
-                    grace_period,
-                    workload,
-                    heart_rate_seconds
-                }
-            },
-            None => {
-                print_block("\n The Workers disklimit in GB (if exceeded don't accept new jobs) ");
-                let disklimit = wizard::differ(self.disklimit, None);
-                print_block("\n The Workers grace period (how long downloaded blendfiles are kept around in seconds - irrelevant for server ");
-                let grace_period = wizard::differ(self.grace_period, None);
-                print_block("\n How many frames should a worker accept at once? ");
-                let workload = wizard::differ(self.workload, None);
-                print_block("\nHow often should the worker send a heartbeat message to bender-qu at max (in seconds)? ");
-                let heart_rate_seconds = wizard::differ(self.heart_rate_seconds, None);
-
-
-                Self{
-                    id: Uuid::new_v4(),
-                    disklimit,
-                    grace_period,
-                    workload,
-                    heart_rate_seconds
-                }
-            }
-        }
-    }
-}
-
-
-
-
-
-
 
 // =============================== UNIT TESTS ================================
 
@@ -902,4 +716,21 @@ mod unit_tests {
             Err(err) => println!("Error while serializing c: {:?}", err)
         }
     }
+
+    #[test]
+    fn from_file_not_found() {
+        match Config::from_file("/this/path/does/not/exist/config.toml"){
+            Err(err) => assert!(err.is_not_found()),
+            Ok(_) => panic!("Deserialized a config from a nonexistent file")
+        }
+    }
+
+    #[test]
+    fn deserialize_parse_error() {
+        match Config::deserialize("servername = [this is not toml"){
+            Err(ConfigError::Parse(_, _)) => (),
+            Err(err) => panic!("Expected a parse error, got: {:?}", err),
+            Ok(_) => panic!("Deserialized a config from invalid TOML")
+        }
+    }
 }
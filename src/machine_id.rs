@@ -0,0 +1,155 @@
+//! The id of the worker identifies this machine towards bender-qu. Besides
+//! the config.toml it is stored in a separate machine-id file, so a machine
+//! keeps its identity if the config is deleted and created again. The wizard
+//! uses the id from that file for new configs and keeps the existing id when a
+//! config is updated, unless the user explicitly asks for a new one.
+use ::*;
+use wizard::Prompter;
+
+
+/// The default location of the machine-id file
+pub const DEFAULT_PATH: &str = "/var/lib/bender/machine-id";
+
+
+/// Read the worker id from the machine-id file. Returns None if there is no
+/// such file
+pub fn read<S>(path: S) -> ConfigResult<Option<Uuid>> where S: Into<String>{
+    let path = path.into();
+    let contents = match fs::read_to_string(path.as_str()){
+        Ok(c) => c,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(ConfigError::from_io(path, err))
+    };
+    Uuid::parse_str(contents.trim())
+        .map(Some)
+        .map_err(|err| ConfigError::InvalidValue(path, contents.trim().to_string(), err.to_string()))
+}
+
+
+/// Store the worker id in the machine-id file, creating its directory if needed
+pub fn write<S>(path: S, id: &Uuid) -> ConfigResult<()> where S: Into<String>{
+    let path = path.into();
+    let mut dir = PathBuf::from(path.as_str());
+    dir.pop();
+    if !dir.as_os_str().is_empty(){
+        fs::create_dir_all(&dir).map_err(|err| ConfigError::from_io(dir.to_string_lossy(), err))?;
+    }
+    atomic::write_atomic_with_mode(path, format!("{}\n", id).as_bytes(), Some(0o644))
+}
+
+
+/// Return the worker id from the machine-id file, or a new random id if there
+/// is no (valid) file
+pub fn load_or_generate<S>(path: S) -> Uuid where S: Into<String>{
+    read(path).ok().and_then(|id| id).unwrap_or_else(Uuid::new_v4)
+}
+
+
+/// The worker id of configs that don't set one: the id from the machine-id
+/// file at `DEFAULT_PATH`, so every load of such a config gets the same id
+pub fn default_id() -> Uuid{
+    load_or_generate(DEFAULT_PATH)
+}
+
+
+impl Config{
+    /// Store the worker id in the machine-id file at `path`
+    pub fn write_machine_id<S>(&self, path: S) -> ConfigResult<()> where S: Into<String>{
+        write(path, &self.worker.id)
+    }
+}
+
+
+/// The dialog for `worker.id` (used via `#[dialog(with = "machine_id::dialog")]`)
+pub mod dialog{
+    use super::*;
+
+    /// New configs use the id from the machine-id file, so a reinstalled
+    /// machine keeps its identity
+    pub fn ask(prompter: &mut dyn Prompter) -> Uuid{
+        match read(DEFAULT_PATH){
+            Ok(Some(id)) => {
+                prompter.print_text(format!("Using the worker id {} from {}", id, DEFAULT_PATH).as_str());
+                id
+            },
+            _ => Uuid::new_v4()
+        }
+    }
+
+    /// Keep the existing id unless the user explicitly chooses another one
+    pub fn compare(prompter: &mut dyn Prompter, this: &Uuid, other: Option<&Uuid>) -> Uuid{
+        let mut items = vec![format!("Keep {}", this)];
+        if let Some(that) = other.filter(|that| *that != this){
+            items.push(format!("Use {}", that));
+        }
+        items.push("Generate a new id (bender-qu will see this worker as a new machine)".to_string());
+        let choice = prompter.select("The id of this worker", &items, 0);
+        match (choice, other){
+            (0, _) => *this,
+            (1, Some(that)) if items.len() == 3 => *that,
+            _ => Uuid::new_v4()
+        }
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use machine_id::*;
+    use wizard::ScriptedPrompter;
+
+    #[test]
+    fn write_and_read() {
+        let mut dir = std::env::temp_dir();
+        dir.push("bender_config_machine_id");
+        let dir = dir.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(dir.as_str());
+        let path = dir.push("nested").push("machine-id");
+
+        assert_eq!(read(path.as_str()).unwrap(), None);
+        let id = Uuid::new_v4();
+        write(path.as_str(), &id).unwrap();
+        assert_eq!(read(path.as_str()).unwrap(), Some(id));
+        assert_eq!(load_or_generate(path.as_str()), id);
+
+        fs::write(path.as_str(), "not a uuid").unwrap();
+        assert!(read(path.as_str()).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_id_from_machine_id_file() {
+        let without_section = Config::deserialize("servername = \"a\"\n").unwrap();
+        let without_id = Config::deserialize("[worker]\nworkload = 2\n").unwrap();
+        match read(DEFAULT_PATH){
+            Ok(Some(id)) => {
+                assert_eq!(without_section.worker.id, id);
+                assert_eq!(without_id.worker.id, id);
+            },
+            // Without a machine-id file there is no stable id to fall back to
+            _ => assert_ne!(without_section.worker.id, without_id.worker.id)
+        }
+    }
+
+    #[test]
+    fn compare_keeps_id() {
+        let this = Uuid::new_v4();
+        let that = Uuid::new_v4();
+        let mut prompter = ScriptedPrompter::new(Vec::<String>::new());
+        assert_eq!(dialog::compare(&mut prompter, &this, Some(&that)), this);
+        let mut prompter = ScriptedPrompter::new(vec!["1"]);
+        assert_eq!(dialog::compare(&mut prompter, &this, Some(&that)), that);
+        let mut prompter = ScriptedPrompter::new(vec!["1"]);
+        let regenerated = dialog::compare(&mut prompter, &this, None);
+        assert!(regenerated != this);
+
+        // The whole worker dialog keeps the id as well
+        let worker = Worker::default();
+        let mut prompter = ScriptedPrompter::new(Vec::<String>::new());
+        assert_eq!(worker.compare(&mut prompter, None).id, worker.id);
+    }
+}
@@ -0,0 +1,389 @@
+//! The bender-config CLI tool creates, updates and inspects the config of the
+//! bender renderfarm. Run `bender-config --help` for the available commands.
+#[macro_use]
+extern crate serde_derive;
+extern crate docopt;
+extern crate bender_config;
+
+use docopt::Docopt;
+use std::process;
+use bender_config::*;
+use bender_config::wizard::{Dialog, TerminalPrompter, errorprint, okprint};
+
+
+const USAGE: &str = "
+bender-config creates, updates and inspects the config of the bender renderfarm
+
+Usage:
+  bender-config init [--config=<path>] [--unattended] [--answers=<file>]
+  bender-config update [--config=<path>]
+  bender-config show [--config=<path>] [--format=<format>]
+  bender-config path [--config=<path>]
+  bender-config validate [--config=<path>]
+  bender-config diff <other> [--config=<path>]
+  bender-config get <key> [--config=<path>]
+  bender-config set <key> <value> [--config=<path>]
+  bender-config backups [--config=<path>]
+  bender-config backups diff <backup> [--config=<path>]
+  bender-config backups restore <backup> [--config=<path>]
+  bender-config schema [--output=<file>]
+  bender-config flask-settings [<file>] [--watch] [--config=<path>]
+  bender-config rotate-secret [--config=<path>]
+  bender-config (-h | --help)
+  bender-config --version
+
+Commands:
+  init        Create a new config.toml with the interactive wizard
+  update      Walk through the values of the existing config.toml and change them
+  show        Print the effective config (including environment overrides)
+  path        Print the path of the config.toml that is used
+  validate    Check the config.toml and the permissions of the app.secret and
+              print all problems
+  diff        Show which values of the config.toml differ from another config file
+  get         Print a single value, e.g. `bender-config get worker.workload`
+  set         Change a single value in the config.toml
+  backups     List, diff or restore backups of the config.toml
+  schema      Print the JSON schema of the current config format
+  flask-settings
+              Write the settings module for flaskbender (default:
+              /etc/bender/flaskbender_settings.py)
+  rotate-secret
+              Replace the app.secret, the previous one stays valid for
+              flaskbender.secret_overlap
+
+Options:
+  -h --help             Show this screen.
+  --version             Show version.
+  -c --config=<path>    Use this config.toml instead of searching for one.
+  --unattended          Don't ask, answer the prompts of init from the
+                        answers file, BENDER_* variables or the defaults.
+  --answers=<file>      TOML or JSON file with answers (implies --unattended).
+  -o --output=<file>    Write the schema to this file instead of stdout.
+  -f --format=<format>  Print the config as toml, json, yaml or env [default: toml].
+  --watch               Keep running and regenerate the file on every change.
+
+Exit codes:
+  0  Success
+  1  Any other error
+  2  Invalid command line
+  3  No config.toml found
+  4  The config.toml is invalid or the app.secret is accessible by others
+  5  Unknown key or invalid value for get/set
+";
+
+
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NOT_FOUND: i32 = 3;
+const EXIT_INVALID: i32 = 4;
+const EXIT_KEY: i32 = 5;
+
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    cmd_init: bool,
+    cmd_update: bool,
+    cmd_show: bool,
+    cmd_path: bool,
+    cmd_validate: bool,
+    cmd_get: bool,
+    cmd_set: bool,
+    cmd_backups: bool,
+    cmd_diff: bool,
+    cmd_restore: bool,
+    cmd_schema: bool,
+    cmd_flask_settings: bool,
+    cmd_rotate_secret: bool,
+    arg_key: String,
+    arg_value: String,
+    arg_backup: String,
+    arg_other: String,
+    arg_file: Option<String>,
+    flag_config: Option<String>,
+    flag_unattended: bool,
+    flag_answers: Option<String>,
+    flag_output: Option<String>,
+    flag_format: String,
+    flag_watch: bool
+}
+
+
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+                            .and_then(|d| d.version(Some(env!("CARGO_PKG_VERSION").to_string())).deserialize())
+                            .unwrap_or_else(|e| {
+                                if e.fatal(){
+                                    eprintln!("{}", e);
+                                    process::exit(EXIT_USAGE);
+                                }
+                                e.exit()
+                            });
+
+    let result = if args.cmd_init{
+        init(&args)
+    }else if args.cmd_update{
+        update(&args)
+    }else if args.cmd_show{
+        show(&args)
+    }else if args.cmd_path{
+        path(&args)
+    }else if args.cmd_validate{
+        validate(&args)
+    }else if args.cmd_get{
+        get(&args)
+    }else if args.cmd_set{
+        set(&args)
+    }else if args.cmd_backups{
+        backups(&args)
+    }else if args.cmd_diff{
+        diff(&args)
+    }else if args.cmd_schema{
+        schema(&args)
+    }else if args.cmd_flask_settings{
+        flask_settings(&args)
+    }else if args.cmd_rotate_secret{
+        rotate_secret(&args)
+    }else{
+        Ok(())
+    };
+
+    if let Err(err) = result{
+        errorprint(err.to_string());
+        process::exit(exit_code(&err));
+    }
+}
+
+
+/// Map a error to the exit code documented in the usage
+fn exit_code(err: &ConfigError) -> i32{
+    match err{
+        ConfigError::NotFound(_) | ConfigError::NotDiscovered(_) => EXIT_NOT_FOUND,
+        ConfigError::Invalid(_) | ConfigError::Parse(_, _) | ConfigError::Decode(_, _) | ConfigError::UnsupportedVersion(_) => EXIT_INVALID,
+        ConfigError::UnknownKey(_) | ConfigError::InvalidValue(_, _, _) => EXIT_KEY,
+        _ => EXIT_ERROR
+    }
+}
+
+
+/// Return the path of the config.toml, either the explicit one or the
+/// discovered one
+fn config_path(args: &Args) -> ConfigResult<String>{
+    Ok(discover(args.flag_config.as_deref())?.path)
+}
+
+
+/// Read the config.toml as it is on disk (without environment overrides)
+fn read(args: &Args) -> ConfigResult<(Config, String)>{
+    let path = config_path(args)?;
+    let (mut config, report) = Config::from_file_migrated(path.as_str())?;
+    if report.migrated(){
+        for step in &report.applied{
+            println!("Migrated {}", step);
+        }
+    }
+    config.paths.config = path.clone();
+    Ok((config, path))
+}
+
+
+fn init(args: &Args) -> ConfigResult<()>{
+    let path = args.flag_config.clone().unwrap_or_else(Config::location);
+    if path.exists(){
+        let err = std::io::Error::new(std::io::ErrorKind::AlreadyExists, "it already exists, use bender-config update to change it");
+        return Err(ConfigError::Io(path, err));
+    }
+    let mut config = if args.flag_unattended || args.flag_answers.is_some(){
+        let answers = match args.flag_answers{
+            Some(ref file) => Answers::from_file(file.as_str())?,
+            None => Answers::new()
+        };
+        let (config, log) = Config::ask_unattended(answers.with_env())?;
+        for prompt in log{
+            println!("{}", prompt);
+        }
+        config
+    }else{
+        Config::ask(&mut TerminalPrompter::new())
+    };
+    config.paths.config = path.clone();
+    for warning in config.ensure_valid()?{
+        println!("{}", warning);
+    }
+    config.write_changes()?;
+    okprint(format!("Wrote config to {}", path));
+    persist_machine_id(&config);
+    if !config.appsecret_exists(){
+        config.write_appsecret()?;
+        okprint(format!("Wrote new app.secret to {}", config.get_appsecret_path()));
+    }
+    Ok(())
+}
+
+
+fn update(args: &Args) -> ConfigResult<()>{
+    let (existing, path) = read(args)?;
+    let mut config = existing.compare(&mut TerminalPrompter::new(), None);
+    config.paths.config = path.clone();
+    for warning in config.ensure_valid()?{
+        println!("{}", warning);
+    }
+    config.write_changes()?;
+    okprint(format!("Wrote config to {}", path));
+    persist_machine_id(&config);
+    Ok(())
+}
+
+
+/// Store the worker id in the machine-id file. This is not fatal, as the
+/// config itself has been written already
+fn persist_machine_id(config: &Config){
+    if machine_id::read(machine_id::DEFAULT_PATH).ok() == Some(Some(config.worker.id)){
+        return;
+    }
+    match config.write_machine_id(machine_id::DEFAULT_PATH){
+        Ok(_) => okprint(format!("Wrote worker id to {}", machine_id::DEFAULT_PATH)),
+        Err(err) => errorprint(format!("Couldn't store the worker id: {}", err))
+    }
+}
+
+
+fn show(args: &Args) -> ConfigResult<()>{
+    let format: Format = args.flag_format.parse()?;
+    let (config, _) = Config::try_get_from(args.flag_config.as_deref())?;
+    print!("{}", config.serialize_as(format)?);
+    Ok(())
+}
+
+
+fn path(args: &Args) -> ConfigResult<()>{
+    println!("{}", config_path(args)?);
+    Ok(())
+}
+
+
+fn validate(args: &Args) -> ConfigResult<()>{
+    let (mut config, path) = read(args)?;
+    config.apply_env()?;
+    let mut issues = config.validate();
+    issues.extend(config.audit_permissions());
+    for issue in &issues{
+        println!("{}", issue);
+    }
+    if issues.iter().any(|i| i.is_error()){
+        return Err(ConfigError::Invalid(issues));
+    }
+    okprint(format!("{} is valid", path));
+    Ok(())
+}
+
+
+fn diff(args: &Args) -> ConfigResult<()>{
+    // Compare the files as they are, read() would overwrite paths.config
+    let path = config_path(args)?;
+    let config = Config::from_file(path.as_str())?;
+    let other = Config::from_file(args.arg_other.as_str())?;
+    let changes = config.diff(&other);
+    if changes.is_empty(){
+        okprint(format!("{} and {} don't differ", path, args.arg_other));
+    }else{
+        print!("{}", diff::unified(&changes, path.as_str(), args.arg_other.as_str()));
+    }
+    Ok(())
+}
+
+
+fn get(args: &Args) -> ConfigResult<()>{
+    let (config, _) = Config::try_get_from(args.flag_config.as_deref())?;
+    println!("{}", config.get_key(args.arg_key.as_str())?);
+    Ok(())
+}
+
+
+fn set(args: &Args) -> ConfigResult<()>{
+    let (mut config, path) = read(args)?;
+    config.set_key(args.arg_key.as_str(), args.arg_value.as_str())?;
+    config.paths.config = path;
+    config.ensure_valid()?;
+    config.write_changes()
+}
+
+
+fn backups(args: &Args) -> ConfigResult<()>{
+    let path = config_path(args)?;
+    let all = backup::list_backups(path.as_str())?;
+    if !args.cmd_diff && !args.cmd_restore{
+        for b in &all{
+            println!("{}  {}", b.timestamp, b.path);
+        }
+        return Ok(());
+    }
+
+    let selected = match all.iter().find(|b| b.timestamp == args.arg_backup || b.path == args.arg_backup){
+        Some(b) => b,
+        None => return Err(ConfigError::NotFound(args.arg_backup.clone()))
+    };
+    if args.cmd_diff{
+        print!("{}", backup::diff_backup(selected, path.as_str())?);
+    }else{
+        // The current config might be broken, that is why it is restored
+        let keep = Config::from_file(path.as_str()).map(|c| c.backup_count)
+                                                   .unwrap_or(Config::default().backup_count);
+        backup::restore_backup(selected, path.as_str(), keep)?;
+        okprint(format!("Restored {} from {}", path, selected.path));
+    }
+    Ok(())
+}
+
+
+fn schema(args: &Args) -> ConfigResult<()>{
+    let schema = format!("{:#}\n", Config::json_schema());
+    match args.flag_output{
+        Some(ref file) => {
+            atomic::write_atomic(file.as_str(), schema.as_bytes())?;
+            okprint(format!("Wrote the JSON schema to {}", file));
+        },
+        None => print!("{}", schema)
+    }
+    Ok(())
+}
+
+
+fn flask_settings(args: &Args) -> ConfigResult<()>{
+    let file = args.arg_file.clone().unwrap_or_else(|| flask::DEFAULT_PATH.to_string());
+    // Both modes read all layers (drop-ins, host file and environment)
+    let builder = ConfigBuilder::new().system_file(config_path(args)?);
+    if !args.flag_watch{
+        let config = builder.build()?;
+        if config.write_flask_settings(file.as_str())?{
+            okprint(format!("Wrote the flaskbender settings to {}", file));
+        }else{
+            okprint(format!("{} is up to date", file));
+        }
+        return Ok(());
+    }
+
+    let target = file.clone();
+    let _watcher = flask::keep_updated(builder, file.as_str(), std::time::Duration::from_secs(2), move |result| {
+        match result{
+            Ok(true) => okprint(format!("Regenerated {}", target)),
+            Ok(false) => (),
+            Err(err) => errorprint(format!("Keeping {} as it is: {}", target, err))
+        }
+    })?;
+    okprint(format!("Wrote {}, watching for changes", file));
+    loop{
+        std::thread::park();
+    }
+}
+
+
+fn rotate_secret(args: &Args) -> ConfigResult<()>{
+    let (config, _) = Config::try_get_from(args.flag_config.as_deref())?;
+    config.rotate_appsecret()?;
+    okprint(format!("Wrote new app.secret to {}", config.get_appsecret_path()));
+    if config.previous_appsecret_expiry()?.is_some(){
+        okprint(format!("The previous secret stays valid for {} as {}", config.flaskbender.secret_overlap, config.get_previous_appsecret_path()));
+    }
+    println!("Restart flaskbender to sign with the new secret");
+    Ok(())
+}
@@ -0,0 +1,267 @@
+//! Upgrades config files written by older versions of bender_config. Every
+//! config.toml carries a `version`, files without one are treated as version
+//! 0. Before deserializing, the document is run through all migrations
+//! starting at its version, each of which upgrades it by exactly one version.
+//! The MigrationReport lists what was changed, so the file can be rewritten.
+use ::*;
+use toml::Value;
+use toml::value::Table;
+
+
+/// The version of the config format written by this version of bender_config
+pub const CURRENT_VERSION: u32 = 6;
+
+
+/// A single migration step from version `from` to `from + 1`
+pub struct Migration{
+    pub from: u32,
+    pub description: &'static str,
+    /// True if the step only adds missing keys with their defaults, these
+    /// steps are skipped by `migrate_partial()`
+    pub adds_defaults: bool,
+    apply: fn(&mut Table)
+}
+
+
+/// Return all known migrations, ordered by version
+pub fn migrations() -> Vec<Migration>{
+    vec![
+        Migration{
+            from: 0,
+            description: "Add a version to the config",
+            adds_defaults: false,
+            apply: |_| ()
+        },
+        Migration{
+            from: 1,
+            description: "Add backup_count (number of backups kept by write_changes)",
+            adds_defaults: true,
+            apply: |table| {
+                table.entry("backup_count".to_string())
+                     .or_insert_with(|| Value::Integer(Config::default().backup_count as i64));
+            }
+        },
+        Migration{
+            from: 2,
+            description: "Replace the durations in seconds and minutes with human readable durations (e.g. \"14d\")",
+            adds_defaults: false,
+            apply: |table| {
+                for (section, old, new, unit) in LEGACY_DURATIONS.iter(){
+                    if let Some(Value::Table(section)) = table.get_mut(*section){
+                        upgrade_integer(section, old, new, &|i| HumanDuration::from_secs(i * unit).to_string());
+                    }
+                }
+            }
+        },
+        Migration{
+            from: 3,
+            description: "Replace the sizes in GB with human readable sizes (e.g. \"500MB\")",
+            adds_defaults: false,
+            apply: |table| {
+                for (section, key) in [("flaskbender", "upload_limit"), ("worker", "disklimit")].iter(){
+                    if let Some(Value::Table(section)) = table.get_mut(*section){
+                        upgrade_integer(section, key, key, &|i| ByteSize::from_gb(i).to_string());
+                    }
+                }
+            }
+        },
+        Migration{
+            from: 4,
+            description: "Add flaskbender.secret_overlap (how long the previous app.secret stays valid after a rotation)",
+            adds_defaults: true,
+            apply: |table| {
+                let overlap = Config::default().flaskbender.secret_overlap.to_string();
+                if let Value::Table(section) = table.entry("flaskbender".to_string()).or_insert_with(|| Value::Table(Table::new())){
+                    section.entry("secret_overlap".to_string()).or_insert(Value::String(overlap));
+                }
+            }
+        },
+        Migration{
+            from: 5,
+            description: "Add paths.private_owner and paths.private_group (owner of the private directory and the app.secret)",
+            adds_defaults: true,
+            apply: |table| {
+                if let Value::Table(section) = table.entry("paths".to_string()).or_insert_with(|| Value::Table(Table::new())){
+                    for key in &["private_owner", "private_group"]{
+                        section.entry(key.to_string()).or_insert_with(|| Value::String(String::new()));
+                    }
+                }
+            }
+        },
+    ]
+}
+
+
+/// The integer durations of version 2: section, old key, new key and the
+/// length of their unit in seconds
+const LEGACY_DURATIONS: [(&str, &str, &str, u64); 9] = [
+    ("janitor", "checking_period_seconds",     "checking_period",     1),
+    ("janitor", "error_deletion_min_minutes",  "error_deletion_min",  60),
+    ("janitor", "error_deletion_max_minutes",  "error_deletion_max",  60),
+    ("janitor", "finish_deletion_min_minutes", "finish_deletion_min", 60),
+    ("janitor", "finish_deletion_max_minutes", "finish_deletion_max", 60),
+    ("janitor", "cancel_deletion_min_minutes", "cancel_deletion_min", 60),
+    ("janitor", "cancel_deletion_max_minutes", "cancel_deletion_max", 60),
+    ("worker",  "grace_period",                "grace_period",        1),
+    ("worker",  "heart_rate_seconds",          "heart_rate",          1),
+];
+
+
+/// The keys renamed by migrations: section, old key and new key
+pub fn renamed_keys() -> Vec<(&'static str, &'static str, &'static str)>{
+    LEGACY_DURATIONS.iter()
+                    .filter(|(_, old, new, _)| old != new)
+                    .map(|(section, old, new, _)| (*section, *old, *new))
+                    .collect()
+}
+
+
+/// Move the integer at `old` to `new` as the string returned by `convert`. A
+/// value that already exists at `new` wins. Negative values are moved as they
+/// are, so deserializing reports them
+fn upgrade_integer(section: &mut Table, old: &str, new: &str, convert: &dyn Fn(u64) -> String){
+    let value = match section.get(old){
+        Some(Value::Integer(i)) if *i >= 0 => Value::String(convert(*i as u64)),
+        Some(Value::Integer(i)) => Value::Integer(*i),
+        _ => return
+    };
+    section.remove(old);
+    section.entry(new.to_string()).or_insert(value);
+}
+
+
+/// What happened while migrating a document
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport{
+    pub from: u32,
+    pub to: u32,
+    pub applied: Vec<String>
+}
+
+impl MigrationReport{
+    /// Returns true if the document was changed by any migration
+    pub fn migrated(&self) -> bool{
+        self.from != self.to
+    }
+}
+
+
+/// Return the version of a toml document, 0 if it has none
+fn version_of(table: &Table) -> ConfigResult<u32>{
+    match table.get("version"){
+        None => Ok(0),
+        Some(v) => match v.as_integer(){
+            Some(i) if i >= 0 && i <= i64::from(CURRENT_VERSION) => Ok(i as u32),
+            Some(i) if i > 0 => Err(ConfigError::UnsupportedVersion(i as u32)),
+            _ => Err(ConfigError::InvalidValue("version".to_string(), v.to_string(), "expected a positive integer".to_string()))
+        }
+    }
+}
+
+
+/// Migrate a toml document to `CURRENT_VERSION` in place
+pub fn migrate(table: &mut Table) -> ConfigResult<MigrationReport>{
+    let from = version_of(table)?;
+    let mut applied = Vec::new();
+    for migration in migrations().iter().filter(|m| m.from >= from){
+        (migration.apply)(table);
+        table.insert("version".to_string(), Value::Integer(i64::from(migration.from + 1)));
+        applied.push(format!("v{} -> v{}: {}", migration.from, migration.from+1, migration.description));
+    }
+
+    Ok(MigrationReport{ from, to: CURRENT_VERSION, applied })
+}
+
+
+/// Migrate a partial document (e.g. a drop-in) in place. Only renames and
+/// conversions are applied and the version is removed, so merging it over
+/// other layers doesn't replace their values with defaults
+pub fn migrate_partial(table: &mut Table) -> ConfigResult<()>{
+    let from = version_of(table)?;
+    for migration in migrations().iter().filter(|m| m.from >= from && !m.adds_defaults){
+        (migration.apply)(table);
+    }
+    table.remove("version");
+    Ok(())
+}
+
+
+/// Parse, migrate and deserialize a Config. `origin` is used in error messages
+pub fn parse<S>(origin: S, contents: &str) -> ConfigResult<(Config, MigrationReport)> where S: Into<String>{
+    let origin = origin.into();
+    let document: Table = toml::from_str(contents)
+                              .map_err(|err| ConfigError::Parse(origin.clone(), err))?;
+    parse_table(origin, document)
+}
+
+
+/// Migrate and deserialize a Config from a already parsed document (e.g. one
+/// read from JSON)
+pub fn parse_table<S>(origin: S, mut document: Table) -> ConfigResult<(Config, MigrationReport)> where S: Into<String>{
+    let report = migrate(&mut document)?;
+    let config = Value::Table(document).try_into()
+                                       .map_err(|err| ConfigError::Parse(origin.into(), err))?;
+    Ok((config, report))
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use migration::*;
+
+    #[test]
+    fn migrate_unversioned() {
+        let (c, report) = parse("test", "servername = \"old\"\n[worker]\nworkload = 3\n").unwrap();
+        assert_eq!(c.version, CURRENT_VERSION);
+        assert_eq!(c.servername, "old");
+        assert_eq!(c.worker.workload, 3);
+        assert_eq!(report.from, 0);
+        assert!(report.migrated());
+        assert_eq!(report.applied.len(), migrations().len());
+    }
+
+    #[test]
+    fn current_is_untouched() {
+        let serialized = Config::default().serialize().unwrap();
+        let (_, report) = parse("test", serialized.as_str()).unwrap();
+        assert!(!report.migrated());
+        assert!(report.applied.is_empty());
+    }
+
+    #[test]
+    fn migrate_units() {
+        let legacy = "version = 2\n\
+                      [janitor]\nchecking_period_seconds = 90\nerror_deletion_max_minutes = 20160\n\
+                      [worker]\ngrace_period = 30\nheart_rate_seconds = 5\ndisklimit = 10\n";
+        let (c, report) = parse("test", legacy).unwrap();
+        assert_eq!(report.applied.len(), 4);
+        assert_eq!(c.janitor.checking_period, HumanDuration::from_secs(90));
+        assert_eq!(c.janitor.error_deletion_max.to_string(), "14d");
+        assert_eq!(c.janitor.error_deletion_min, Janitor::default().error_deletion_min);
+        assert_eq!(c.worker.grace_period.as_secs(), 30);
+        assert_eq!(c.worker.heart_rate.as_std(), std::time::Duration::from_secs(5));
+        assert_eq!(c.worker.disklimit.as_bytes(), 10_000_000_000);
+        assert_eq!(c.flaskbender.upload_limit, Flaskbender::default().upload_limit);
+    }
+
+    #[test]
+    fn partial_adds_nothing() {
+        let mut partial: Table = toml::from_str("[worker]\nheart_rate_seconds = 5\n").unwrap();
+        migrate_partial(&mut partial).unwrap();
+        let expected: Table = toml::from_str("[worker]\nheart_rate = \"5s\"\n").unwrap();
+        assert_eq!(partial, expected);
+    }
+
+    #[test]
+    fn newer_version() {
+        let newer = format!("version = {}\n", CURRENT_VERSION+1);
+        match parse("test", newer.as_str()){
+            Err(ConfigError::UnsupportedVersion(v)) => assert_eq!(v, CURRENT_VERSION+1),
+            other => panic!("Expected a UnsupportedVersion error, got {:?}", other)
+        }
+    }
+}
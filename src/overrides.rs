@@ -0,0 +1,168 @@
+//! Environment variable overrides for single config values. This allows e.g.
+//! systemd units or containers to change a value without touching the
+//! config.toml. A variable is mapped onto a field of the Config like this:
+//! ```ignore
+//! BENDER_SERVERNAME         -> servername
+//! BENDER_WORKER__WORKLOAD   -> worker.workload
+//! BENDER_RABBITMQ__URL      -> rabbitmq.url
+//! ```
+//! The value of the variable is parsed into the type of the field (see the
+//! `keys` module). Variables that don't start with a section or top level
+//! field (like `BENDER_HOME`) are ignored. Unknown fields within a known
+//! section (like `BENDER_WORKER__WORKLOADS`) are an error, so typos don't go
+//! unnoticed.
+use ::*;
+use std::env;
+use toml::Value;
+
+
+/// Prefix of all environment variables that override config values
+pub const ENV_PREFIX: &str = "BENDER_";
+
+/// Separates the section from the field name in a environment variable
+pub const ENV_SEPARATOR: &str = "__";
+
+
+/// Turn the name of a environment variable into a dotted key (e.g.
+/// `BENDER_WORKER__WORKLOAD` into `worker.workload`). Returns None if the
+/// variable doesn't look like a override
+pub fn env_to_key(name: &str) -> Option<String>{
+    if !name.starts_with(ENV_PREFIX) || name == discovery::ENV_VAR{
+        return None;
+    }
+    let rest = &name[ENV_PREFIX.len()..];
+    if rest.is_empty(){
+        return None;
+    }
+    Some(rest.to_lowercase().replace(ENV_SEPARATOR, "."))
+}
+
+
+/// Turn a dotted key into the name of its environment variable (e.g.
+/// `worker.workload` into `BENDER_WORKER__WORKLOAD`)
+pub fn key_to_env(key: &str) -> String{
+    format!("{}{}", ENV_PREFIX, key.to_uppercase().replace('.', ENV_SEPARATOR))
+}
+
+
+/// Apply the given `(key, raw value)` pairs to the config
+pub fn apply<I, K, V>(config: &Config, pairs: I) -> ConfigResult<Config>
+    where I: IntoIterator<Item=(K, V)>, K: AsRef<str>, V: AsRef<str>{
+    let mut root = Value::try_from(config)?;
+    for (key, raw) in pairs{
+        keys::set_checked(&mut root, key.as_ref(), raw.as_ref())?;
+    }
+    root.try_into().map_err(|err| ConfigError::Parse("overrides".to_string(), err))
+}
+
+
+/// Return the dotted key if the variable `name` is a override for `root`, the
+/// toml Value representation of the Config the overrides will be applied to.
+/// Variables without a separator are only considered if they name a top level
+/// value (like `BENDER_SERVERNAME`), variables with a separator only if they
+/// start with a section or top level value (like `BENDER_WORKER__`), so
+/// unrelated variables are ignored
+pub fn override_key(root: &Value, name: &str) -> Option<String>{
+    let key = env_to_key(name)?;
+    if name.contains(ENV_SEPARATOR){
+        let first = key.split('.').next().unwrap_or("");
+        root.get(first)?;
+    }else{
+        match root.get(key.as_str()){
+            Some(v) if !v.is_table() => (),
+            _ => return None
+        }
+    }
+    Some(key)
+}
+
+
+/// Collect all overrides from the given environment variables, see
+/// `override_key()` for which variables are considered
+pub fn collect<I>(root: &Value, vars: I) -> Vec<(String, String)>
+    where I: IntoIterator<Item=(String, String)>{
+    let mut overrides: Vec<(String, String, String)> = vars.into_iter()
+        .filter_map(|(name, value)| {
+            let key = override_key(root, name.as_str())?;
+            Some((name, key, value))
+        })
+        .collect();
+    // Sort by variable name to get the same result regardless of the order
+    // of the environment
+    overrides.sort();
+    overrides.into_iter().map(|(_, key, value)| (key, value)).collect()
+}
+
+
+impl Config{
+    /// Apply all `BENDER_*` environment variable overrides to the Config and
+    /// return the dotted keys that have been overridden
+    pub fn apply_env(&mut self) -> ConfigResult<Vec<String>>{
+        self.apply_env_from(env::vars())
+    }
+
+    /// Like `Config::apply_env()` but with the given variables instead of the
+    /// environment of the process
+    pub fn apply_env_from<I>(&mut self, vars: I) -> ConfigResult<Vec<String>>
+        where I: IntoIterator<Item=(String, String)>{
+        let overrides = collect(&Value::try_from(&*self)?, vars);
+        *self = apply(self, overrides.iter().map(|(k, v)| (k.as_str(), v.as_str())))?;
+        Ok(overrides.into_iter().map(|(key, _)| key).collect())
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use overrides::*;
+
+    fn vars(v: &[(&str, &str)]) -> Vec<(String, String)>{
+        v.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn env_names() {
+        assert_eq!(env_to_key("BENDER_WORKER__WORKLOAD"), Some("worker.workload".to_string()));
+        assert_eq!(env_to_key("BENDER_SERVERNAME"), Some("servername".to_string()));
+        assert_eq!(env_to_key("BENDER_CONFIG"), None);
+        assert_eq!(key_to_env("worker.workload"), "BENDER_WORKER__WORKLOAD");
+        assert_eq!(env_to_key("HOME"), None);
+    }
+
+    #[test]
+    fn apply_overrides() {
+        let mut c = Config::default();
+        let applied = c.apply_env_from(vars(&[("BENDER_WORKER__WORKLOAD", "4"),
+                                              ("BENDER_RABBITMQ__URL", "amqp://queue//"),
+                                              ("BENDER_SERVERNAME", "render.example"),
+                                              ("BENDER_UNRELATED", "ignored"),
+                                              ("BENDER_FOO__BAR", "ignored"),
+                                              ("BENDER_QU__HOST", "ignored"),
+                                              ("PATH", "/usr/bin")])).unwrap();
+        assert_eq!(applied.len(), 3);
+        assert_eq!(c.worker.workload, 4);
+        assert_eq!(c.rabbitmq.url, "amqp://queue//");
+        assert_eq!(c.servername, "render.example");
+    }
+
+    #[test]
+    fn bad_values() {
+        let mut c = Config::default();
+        match c.apply_env_from(vars(&[("BENDER_WORKER__WORKLOAD", "many")])){
+            Err(ConfigError::InvalidValue(key, _, _)) => assert_eq!(key, "worker.workload"),
+            other => panic!("Expected a InvalidValue error, got {:?}", other)
+        }
+        match c.apply_env_from(vars(&[("BENDER_JANITOR__CHECKING_PERIOD", "-1")])){
+            Err(ConfigError::InvalidValue(key, _, _)) => assert_eq!(key, "janitor.checking_period"),
+            other => panic!("Expected a InvalidValue error, got {:?}", other)
+        }
+        match c.apply_env_from(vars(&[("BENDER_WORKER__WORKLOADS", "1")])){
+            Err(ConfigError::UnknownKey(key)) => assert_eq!(key, "worker.workloads"),
+            other => panic!("Expected a UnknownKey error, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Strict permissions for the private directory and the secrets in it. The
+//! app.secret and app.secret.prev are always written with mode 0600, owned
+//! by `paths.private_owner` and `paths.private_group` if those are set. The
+//! private directory is created with mode 0700, or 0750 if a group is set,
+//! a existing one that others (or the group) could write to is tightened.
+//! `Config::audit_permissions()` reports existing files with looser modes:
+//! ```ignore
+//! for issue in config.audit_permissions(){
+//!     println!("{}", issue);
+//! }
+//! ```
+//! Owner and group are names (looked up in /etc/passwd and /etc/group) or
+//! numeric ids. Changing the owner usually requires root.
+use ::*;
+use std::io;
+
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+
+/// The mode of the app.secret and app.secret.prev
+pub const SECRET_MODE: u32 = 0o600;
+
+/// The mode of the private directory if no group is set
+pub const PRIVATE_DIR_MODE: u32 = 0o700;
+
+/// The mode of the private directory if a group is set
+pub const SHARED_PRIVATE_DIR_MODE: u32 = 0o750;
+
+/// Mode bits the private directory must not have: the group may enter it,
+/// but not write to it
+const PRIVATE_DIR_FORBIDDEN: u32 = 0o027;
+
+
+/// Resolve the name or uid of a user. Empty names resolve to None
+pub fn resolve_user(name: &str) -> ConfigResult<Option<u32>>{
+    resolve("paths.private_owner", "/etc/passwd", name)
+}
+
+
+/// Resolve the name or gid of a group. Empty names resolve to None
+pub fn resolve_group(name: &str) -> ConfigResult<Option<u32>>{
+    resolve("paths.private_group", "/etc/group", name)
+}
+
+
+/// Look up `name` in a passwd or group style `database`
+fn resolve(key: &str, database: &str, name: &str) -> ConfigResult<Option<u32>>{
+    let name = name.trim();
+    if name.is_empty(){
+        return Ok(None);
+    }
+    if let Ok(id) = name.parse::<u32>(){
+        return Ok(Some(id));
+    }
+    let entries = fs::read_to_string(database).map_err(|err| ConfigError::from_io(database, err))?;
+    // Both have the format name:password:id:...
+    entries.lines()
+           .map(|line| line.split(':').collect::<Vec<&str>>())
+           .find(|fields| fields.len() > 2 && fields[0] == name)
+           .and_then(|fields| fields[2].parse().ok())
+           .map(Some)
+           .ok_or_else(|| ConfigError::InvalidValue(key.to_string(), name.to_string(), format!("neither a id nor a name in {}", database)))
+}
+
+
+impl Config{
+    /// The mode of the private directory, 0750 if a group is set and 0700
+    /// otherwise
+    pub fn private_dir_mode(&self) -> u32{
+        if self.paths.private_group.trim().is_empty(){
+            PRIVATE_DIR_MODE
+        }else{
+            SHARED_PRIVATE_DIR_MODE
+        }
+    }
+
+    /// Create the private directory if it doesn't exist. Only the directory
+    /// itself gets the strict mode and owner, not its parents. A existing
+    /// directory with a looser mode (e.g. 2775 from `is_writeable()`) or
+    /// another owner is fixed as well
+    pub fn create_private_dir(&self) -> ConfigResult<()>{
+        let dir = PathBuf::from(self.paths.private.as_str());
+        let uid = resolve_user(self.paths.private_owner.as_str())?;
+        let gid = resolve_group(self.paths.private_group.as_str())?;
+        let io_error = |err| ConfigError::from_io(self.paths.private.as_str(), err);
+        if !dir.is_dir(){
+            if let Some(parent) = dir.parent(){
+                fs::create_dir_all(parent).map_err(io_error)?;
+            }
+            let mut builder = DirBuilder::new();
+            #[cfg(unix)]
+            builder.mode(self.private_dir_mode());
+            match builder.create(&dir){
+                Ok(_) => (),
+                // Created in the meantime, its mode is checked below
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(err) => return Err(io_error(err))
+            }
+        }
+
+        #[cfg(unix)]
+        {
+            // The mode passed to create is masked by the umask, so the mode
+            // is always checked. Only change what is wrong, as that usually
+            // needs to be the owner (or root)
+            let metadata = fs::metadata(&dir).map_err(io_error)?;
+            let mode = metadata.permissions().mode() & 0o7777;
+            if mode & PRIVATE_DIR_FORBIDDEN != 0{
+                fs::set_permissions(&dir, fs::Permissions::from_mode(self.private_dir_mode())).map_err(io_error)?;
+            }
+            if uid.filter(|uid| *uid != metadata.uid()).is_some() || gid.filter(|gid| *gid != metadata.gid()).is_some(){
+                std::os::unix::fs::chown(&dir, uid, gid).map_err(io_error)?;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (uid, gid);
+        Ok(())
+    }
+
+    /// Returns Ok(true) if the app.secret can be written. Unlike
+    /// `paths.private.is_writeable()`, which creates missing directories
+    /// shared (mode 2775), the private directory gets its strict mode. As the
+    /// app.secret is written atomically, this checks if a file can be created
+    /// next to it, without creating the app.secret itself
+    pub fn private_is_writeable(&self) -> ConfigResult<bool>{
+        match self.create_private_dir(){
+            Ok(_) => (),
+            Err(ref err) if err.is_permission_denied() => return Ok(false),
+            Err(err) => return Err(err)
+        }
+        let probe = atomic::temporary_path(&PathBuf::from(self.get_appsecret_path()));
+        match fs::OpenOptions::new().write(true).create_new(true).open(&probe){
+            Ok(_) => {
+                fs::remove_file(&probe).map_err(|err| ConfigError::from_io(probe.to_string_lossy(), err))?;
+                Ok(true)
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::PermissionDenied => Ok(false),
+            Err(err) => Err(ConfigError::from_io(probe.to_string_lossy(), err))
+        }
+    }
+
+    /// Atomically write a secret to `path` with mode 0600 and the configured
+    /// owner, creating the private directory first
+    pub(crate) fn write_secret<S>(&self, path: S, contents: &[u8]) -> ConfigResult<()> where S: Into<String>{
+        self.create_private_dir()?;
+        let uid = resolve_user(self.paths.private_owner.as_str())?;
+        let gid = resolve_group(self.paths.private_group.as_str())?;
+        atomic::write_atomic_strict(path, contents, SECRET_MODE, uid, gid)
+    }
+
+    /// Check mode and owner of the private directory and the secrets in it.
+    /// Files that are accessible by others are errors, the wrong owner is a
+    /// warning. Missing files are fine
+    pub fn audit_permissions(&self) -> Vec<Issue>{
+        let mut issues = Vec::new();
+        let uid = resolve_user(self.paths.private_owner.as_str()).unwrap_or_else(|err| {
+            issues.push(Issue::warning("paths.private_owner", err.to_string()));
+            None
+        });
+        let gid = resolve_group(self.paths.private_group.as_str()).unwrap_or_else(|err| {
+            issues.push(Issue::warning("paths.private_group", err.to_string()));
+            None
+        });
+        audit_path(&mut issues, self.paths.private.as_str(), self.private_dir_mode(), PRIVATE_DIR_FORBIDDEN, uid, gid);
+        for secret in &[self.get_appsecret_path(), self.get_previous_appsecret_path()]{
+            audit_path(&mut issues, secret.as_str(), SECRET_MODE, 0o077, uid, gid);
+        }
+        issues
+    }
+}
+
+
+/// Report if `path` has any of the `forbidden` mode bits set or isn't owned
+/// by `uid` and `gid`
+#[cfg(unix)]
+fn audit_path(issues: &mut Vec<Issue>, path: &str, expected: u32, forbidden: u32, uid: Option<u32>, gid: Option<u32>){
+    let metadata = match fs::metadata(path){
+        Ok(metadata) => metadata,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return,
+        Err(err) => {
+            issues.push(Issue::warning("paths.private", format!("couldn't check the permissions of {}: {}", path, err)));
+            return;
+        }
+    };
+    let mode = metadata.permissions().mode() & 0o7777;
+    if mode & forbidden != 0{
+        issues.push(Issue::error("paths.private", format!("{} has mode {:04o}, expected {:04o}", path, mode, expected)));
+    }
+    if let Some(uid) = uid.filter(|uid| *uid != metadata.uid()){
+        issues.push(Issue::warning("paths.private_owner", format!("{} is owned by uid {} instead of {}", path, metadata.uid(), uid)));
+    }
+    if let Some(gid) = gid.filter(|gid| *gid != metadata.gid()){
+        issues.push(Issue::warning("paths.private_group", format!("{} belongs to gid {} instead of {}", path, metadata.gid(), gid)));
+    }
+}
+
+#[cfg(not(unix))]
+fn audit_path(_issues: &mut Vec<Issue>, _path: &str, _expected: u32, _forbidden: u32, _uid: Option<u32>, _gid: Option<u32>){
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use permissions::*;
+
+    #[test]
+    fn resolve_ids() {
+        assert_eq!(resolve_user("").unwrap(), None);
+        assert_eq!(resolve_user("1000").unwrap(), Some(1000));
+        assert_eq!(resolve_user("root").unwrap(), Some(0));
+        assert_eq!(resolve_group("root").unwrap(), Some(0));
+        match resolve_group("no-such-group-for-bender"){
+            Err(ConfigError::InvalidValue(key, _, _)) => assert_eq!(key, "paths.private_group"),
+            other => panic!("Expected a InvalidValue error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn strict_secrets_and_audit() {
+        let mut dir = std::env::temp_dir();
+        dir.push("bender_config_permissions");
+        let dir = dir.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(dir.as_str());
+        let mut c = Config::default();
+        c.paths.private = dir.push("private");
+
+        // A shared directory (e.g. from is_writeable()) is tightened on the
+        // first check or write, without creating the app.secret
+        assert!(c.paths.private.is_writeable().unwrap());
+        let mode = |path: String| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        assert_ne!(mode(c.paths.private.clone()), 0o700);
+        assert!(c.private_is_writeable().unwrap());
+        assert_eq!(mode(c.paths.private.clone()), 0o700);
+        assert!(!c.appsecret_exists());
+        assert_eq!(fs::read_dir(c.paths.private.as_str()).unwrap().count(), 0);
+        c.write_appsecret().unwrap();
+        assert_eq!(mode(c.get_appsecret_path()), 0o600);
+        assert!(c.audit_permissions().is_empty());
+
+        fs::set_permissions(c.get_appsecret_path(), fs::Permissions::from_mode(0o644)).unwrap();
+        fs::set_permissions(c.paths.private.as_str(), fs::Permissions::from_mode(0o750)).unwrap();
+        let issues = c.audit_permissions();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].is_error());
+        assert!(issues[0].message.ends_with("has mode 0644, expected 0600"));
+
+        // Rewriting it restores the strict mode
+        c.rotate_appsecret().unwrap();
+        assert_eq!(mode(c.get_appsecret_path()), 0o600);
+        assert_eq!(mode(c.get_previous_appsecret_path()), 0o600);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
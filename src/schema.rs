@@ -0,0 +1,204 @@
+//! A JSON schema (draft 7) of the config format, for the web frontend and ops
+//! tooling that want to validate or autocomplete edits of a config.toml. The
+//! descriptions are the texts and prompts of the wizard, the defaults come
+//! from `Config::default()` and the bounds follow the `validation` module.
+//! The schema only describes the current version of the format (see
+//! `CURRENT_VERSION`, which is stamped into it). Files of older versions
+//! (e.g. with `checking_period_seconds`) don't validate until they have been
+//! migrated, e.g. by `bender-config update`:
+//! ```ignore
+//! let schema = Config::json_schema();
+//! fs::write("bender-config.schema.json", format!("{:#}", schema))?;
+//! ```
+use ::*;
+use serde_json::{Map, Value};
+use units::{DURATION_PATTERN, SIZE_PATTERN};
+use wizard::Dialog;
+
+
+/// The JSON schema dialect of the generated schema
+pub const SCHEMA_DRAFT: &str = "http://json-schema.org/draft-07/schema#";
+
+
+impl Config{
+    /// Return a JSON schema describing the Config and all of its sections
+    pub fn json_schema() -> Value{
+        let defaults = match serde_json::to_value(Config::default()){
+            Ok(Value::Object(fields)) => fields,
+            _ => unreachable!("a Config always serializes to a object")
+        };
+        let descriptions = Config::descriptions();
+        let mut schema = Map::new();
+        schema.insert("$schema".to_string(), Value::from(SCHEMA_DRAFT));
+        schema.insert("title".to_string(), Value::from("bender config"));
+        schema.insert("$comment".to_string(), Value::from(format!("Version {} of the config format, older versions have to be migrated first", CURRENT_VERSION)));
+        if let Some(text) = describe("", &descriptions){
+            schema.insert("description".to_string(), Value::from(text));
+        }
+        schema.extend(object_schema("", &defaults, &descriptions));
+        Value::Object(schema)
+    }
+}
+
+
+/// The descriptions of the section called `name`
+fn section_descriptions(name: &str) -> Vec<(&'static str, &'static str)>{
+    match name{
+        "paths"       => Paths::descriptions(),
+        "flaskbender" => Flaskbender::descriptions(),
+        "rabbitmq"    => RabbitMQ::descriptions(),
+        "janitor"     => Janitor::descriptions(),
+        "worker"      => Worker::descriptions(),
+        _ => Vec::new()
+    }
+}
+
+
+/// Return the description of the field `name`
+fn describe(name: &str, descriptions: &[(&'static str, &'static str)]) -> Option<&'static str>{
+    descriptions.iter().find(|(n, _)| *n == name).map(|(_, text)| text.trim())
+}
+
+
+/// The schema of a table whose fields have the given default values. The
+/// dotted key of the table is `prefix`
+fn object_schema(prefix: &str, fields: &Map<String, Value>, descriptions: &[(&'static str, &'static str)]) -> Map<String, Value>{
+    let mut properties = Map::new();
+    for (name, default) in fields{
+        let key = if prefix.is_empty(){ name.clone() }else{ format!("{}.{}", prefix, name) };
+        let mut property = match default{
+            Value::Object(section) => {
+                let section_descriptions = section_descriptions(name);
+                let mut property = object_schema(key.as_str(), section, &section_descriptions);
+                if let Some(text) = describe("", &section_descriptions){
+                    property.insert("description".to_string(), Value::from(text));
+                }
+                property
+            },
+            leaf => value_schema(key.as_str(), leaf)
+        };
+        // The description of the parent wins over the one of the section
+        if let Some(text) = describe(name, descriptions){
+            property.insert("description".to_string(), Value::from(text));
+        }
+        properties.insert(name.clone(), Value::Object(property));
+    }
+    let mut schema = Map::new();
+    schema.insert("type".to_string(), Value::from("object"));
+    schema.insert("properties".to_string(), Value::Object(properties));
+    schema.insert("additionalProperties".to_string(), Value::from(false));
+    schema
+}
+
+
+/// The schema of the value at the dotted `key`
+fn value_schema(key: &str, default: &Value) -> Map<String, Value>{
+    let mut schema = Map::new();
+    match default{
+        Value::String(_) => { schema.insert("type".to_string(), Value::from("string")); },
+        Value::Bool(_)   => { schema.insert("type".to_string(), Value::from("boolean")); },
+        Value::Number(n) if n.is_f64() => { schema.insert("type".to_string(), Value::from("number")); },
+        // All integers of the Config are unsigned
+        Value::Number(_) => {
+            schema.insert("type".to_string(), Value::from("integer"));
+            schema.insert("minimum".to_string(), Value::from(0));
+        },
+        _ => ()
+    }
+    schema.insert("default".to_string(), default.clone());
+
+    match key{
+        "janitor.checking_period" | "worker.heart_rate" => duration(&mut schema, true),
+        "janitor.error_deletion_min" | "janitor.error_deletion_max" |
+        "janitor.finish_deletion_min" | "janitor.finish_deletion_max" |
+        "janitor.cancel_deletion_min" | "janitor.cancel_deletion_max" |
+        "worker.grace_period" | "flaskbender.secret_overlap" => duration(&mut schema, false),
+        "flaskbender.upload_limit" | "worker.disklimit" => size(&mut schema),
+        "version" => {
+            schema.insert("const".to_string(), Value::from(CURRENT_VERSION));
+        },
+        "worker.workload" => {
+            schema.insert("minimum".to_string(), Value::from(1));
+        },
+        "worker.id" => {
+            // Every worker gets its own random id
+            schema.remove("default");
+            schema.insert("format".to_string(), Value::from("uuid"));
+        },
+        "rabbitmq.url" => url(&mut schema, "^amqps?://"),
+        "flaskbender.upload_url" => url(&mut schema, "^https?://"),
+        "paths.config" | "paths.private" | "paths.upload" | "flaskbender.job_cookie_name" => {
+            schema.insert("pattern".to_string(), Value::from(r"\S"));
+        },
+        _ => ()
+    }
+    schema
+}
+
+
+/// A HumanDuration, or a number of seconds
+fn duration(schema: &mut Map<String, Value>, non_zero: bool){
+    schema.insert("type".to_string(), json!(["string", "integer"]));
+    schema.insert("pattern".to_string(), Value::from(DURATION_PATTERN));
+    schema.insert("minimum".to_string(), Value::from(if non_zero { 1 }else{ 0 }));
+    schema.insert("examples".to_string(), json!(["90s", "1h30m", "14d"]));
+    if non_zero{
+        schema.insert("not".to_string(), json!({"type": "string", "pattern": r"^[0mswdh\s]*$"}));
+    }
+}
+
+
+/// A ByteSize, or a number of GB
+fn size(schema: &mut Map<String, Value>){
+    schema.insert("type".to_string(), json!(["string", "integer"]));
+    schema.insert("pattern".to_string(), Value::from(SIZE_PATTERN));
+    schema.insert("minimum".to_string(), Value::from(0));
+    schema.insert("examples".to_string(), json!(["500MB", "2GB", "2GiB"]));
+}
+
+
+/// A URL with a scheme matching `scheme_pattern`
+fn url(schema: &mut Map<String, Value>, scheme_pattern: &str){
+    schema.insert("format".to_string(), Value::from("uri"));
+    schema.insert("pattern".to_string(), Value::from(scheme_pattern));
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use schema::*;
+
+    #[test]
+    fn descriptions_defaults_and_bounds() {
+        let schema = Config::json_schema();
+        assert_eq!(schema["$schema"], SCHEMA_DRAFT);
+        assert!(schema["$comment"].as_str().unwrap().starts_with(format!("Version {} ", CURRENT_VERSION).as_str()));
+        assert_eq!(schema["properties"]["version"]["const"], CURRENT_VERSION);
+        let worker = &schema["properties"]["worker"];
+        assert!(worker["description"].as_str().unwrap().starts_with("The bender-worker is the client"));
+        assert_eq!(worker["additionalProperties"], false);
+        let workload = &worker["properties"]["workload"];
+        assert_eq!(workload["description"], "How many frames should the worker render at once?");
+        assert_eq!(workload["type"], "integer");
+        assert_eq!(workload["minimum"], 1);
+        assert_eq!(workload["default"], 1);
+        assert_eq!(worker["properties"]["id"]["format"], "uuid");
+        assert!(worker["properties"]["id"].get("default").is_none());
+        assert_eq!(schema["properties"]["janitor"]["properties"]["checking_period"]["default"], "1m");
+        assert_eq!(schema["properties"]["paths"]["description"], "Where the config, the app.secret and the uploaded files are stored");
+    }
+
+    #[test]
+    fn covers_every_key() {
+        let schema = Config::json_schema();
+        for key in Config::default().keys(){
+            let property = key.split('.').fold(&schema, |s, part| &s["properties"][part]);
+            assert!(property["type"].is_string() || property["type"].is_array(), "{} has no type", key);
+            assert!(property["description"].is_string(), "{} has no description", key);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Rotation of the app.secret. Replacing it with `write_appsecret()` instantly
+//! invalidates every job cookie flaskbender signed with the old secret, so
+//! `rotate_appsecret()` keeps the old one as `app.secret.prev` instead. The
+//! previous secret stays valid for `flaskbender.secret_overlap` after the
+//! rotation (judged by the modification time of `app.secret.prev`):
+//! ```ignore
+//! config.rotate_appsecret()?;
+//! // Sign with the current secret, but accept signatures of all of them
+//! let secrets = config.read_appsecrets()?;
+//! let valid = secrets.all().iter().any(|secret| verify(cookie, secret));
+//! ```
+use ::*;
+use std::io;
+use std::time::SystemTime;
+
+
+/// Appended to the path of the app.secret for the previous secret
+pub const PREVIOUS_SUFFIX: &str = ".prev";
+
+
+/// The app secrets that are valid right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppSecrets{
+    /// The secret new signatures are made with
+    pub current: String,
+    /// The secret before the last rotation, if its overlap hasn't expired yet
+    pub previous: Option<String>
+}
+
+
+impl AppSecrets{
+    /// All valid secrets for verifying signatures, the current one first
+    pub fn all(&self) -> Vec<&str>{
+        let mut secrets = vec![self.current.as_str()];
+        if let Some(ref previous) = self.previous{
+            secrets.push(previous.as_str());
+        }
+        secrets
+    }
+}
+
+
+impl Config{
+    /// Gets the path of the previous appsecret (app.secret.prev next to the
+    /// app.secret)
+    pub fn get_previous_appsecret_path(&self) -> String{
+        format!("{}{}", self.get_appsecret_path(), PREVIOUS_SUFFIX)
+    }
+
+    /// Replace the appsecret with a new one and keep the old one as previous
+    /// appsecret. Without an existing appsecret this just writes a new one
+    pub fn rotate_appsecret(&self) -> ConfigResult<()>{
+        match self.read_appsecret(){
+            Ok(current) => self.write_secret(self.get_previous_appsecret_path(), current.as_bytes())?,
+            Err(ref err) if err.is_not_found() => (),
+            Err(err) => return Err(err)
+        }
+        self.write_appsecret()
+    }
+
+    /// Returns when the previous appsecret stops being valid, or None if
+    /// there is no previous appsecret
+    pub fn previous_appsecret_expiry(&self) -> ConfigResult<Option<SystemTime>>{
+        let path = self.get_previous_appsecret_path();
+        match fs::metadata(path.as_str()).and_then(|m| m.modified()){
+            Ok(rotated) => Ok(Some(rotated + self.flaskbender.secret_overlap.as_std())),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(ConfigError::from_io(path, err))
+        }
+    }
+
+    /// Reads the current appsecret and the previous one, as long as it is
+    /// within its overlap
+    pub fn read_appsecrets(&self) -> ConfigResult<AppSecrets>{
+        let current = self.read_appsecret()?;
+        let previous = match self.previous_appsecret_expiry()?{
+            Some(expiry) if SystemTime::now() < expiry => {
+                let path = self.get_previous_appsecret_path();
+                match fs::read_to_string(path.as_str()){
+                    Ok(previous) => Some(previous),
+                    // Removed in the meantime
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+                    Err(err) => return Err(ConfigError::from_io(path, err))
+                }
+            },
+            _ => None
+        };
+        Ok(AppSecrets{ current, previous })
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use secret::*;
+
+    fn private_dir(name: &str) -> Config{
+        let mut dir = std::env::temp_dir();
+        dir.push(name);
+        let dir = dir.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(dir.as_str()).unwrap();
+        let mut c = Config::default();
+        c.paths.private = dir;
+        c
+    }
+
+    #[test]
+    fn rotate_keeps_previous() {
+        let c = private_dir("bender_config_rotate");
+        c.rotate_appsecret().unwrap();
+        let first = c.read_appsecrets().unwrap();
+        assert!(first.previous.is_none());
+        assert_eq!(first.all().len(), 1);
+
+        c.rotate_appsecret().unwrap();
+        let second = c.read_appsecrets().unwrap();
+        assert_ne!(second.current, first.current);
+        assert_eq!(second.previous, Some(first.current.clone()));
+        assert_eq!(second.all(), vec![second.current.as_str(), first.current.as_str()]);
+        assert!(c.get_previous_appsecret_path().ends_with("app.secret.prev"));
+        fs::remove_dir_all(c.paths.private).unwrap();
+    }
+
+    #[test]
+    fn previous_expires() {
+        let mut c = private_dir("bender_config_rotate_expired");
+        c.flaskbender.secret_overlap = HumanDuration::from_secs(0);
+        assert_eq!(c.previous_appsecret_expiry().unwrap(), None);
+        c.write_appsecret().unwrap();
+        c.rotate_appsecret().unwrap();
+        assert!(c.previous_appsecret_expiry().unwrap().is_some());
+        assert!(c.read_appsecrets().unwrap().previous.is_none());
+        fs::remove_dir_all(c.paths.private).unwrap();
+    }
+}
@@ -0,0 +1,381 @@
+//! Config values with a unit. Instead of encoding the unit in the name of a
+//! field (`error_deletion_max_minutes = 20160`) the value carries it:
+//! ```ignore
+//! [janitor]
+//! checking_period = "90s"
+//! error_deletion_max = "14d"
+//! finish_deletion_min = "1h30m"
+//! ```
+//! Services get the plain `std::time::Duration` via `as_std()`. Sizes work
+//! the same way, `upload_limit = "500MB"` or `disklimit = "2GiB"`, and are
+//! handed out as number of bytes via `as_bytes()`.
+use std::fmt;
+use std::str::FromStr;
+use std::time;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, Visitor};
+
+
+/// The units a HumanDuration can be written in, with their length in
+/// milliseconds. Ordered from the largest to the smallest, weeks are only
+/// accepted when parsing
+const DURATION_UNITS: [(&str, u64); 6] = [("w", 7*24*60*60*1000),
+                                          ("d", 24*60*60*1000),
+                                          ("h", 60*60*1000),
+                                          ("m", 60*1000),
+                                          ("s", 1000),
+                                          ("ms", 1)];
+
+
+/// A regular expression matching everything `HumanDuration` parses (used by
+/// the JSON schema)
+pub const DURATION_PATTERN: &str = r"^\s*(\d+|(\d+(ms|w|d|h|m|s)\s*)+)\s*$";
+
+
+/// A duration written like `"14d"`, `"90s"` or `"1h30m"`. Plain numbers are
+/// read as seconds. Displayed and serialized in the same notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct HumanDuration(time::Duration);
+
+
+impl HumanDuration{
+    pub fn from_secs(secs: u64) -> Self{
+        HumanDuration(time::Duration::from_secs(secs))
+    }
+
+    pub fn from_mins(mins: u64) -> Self{
+        Self::from_secs(mins*60)
+    }
+
+    /// Return the duration as `std::time::Duration`
+    pub fn as_std(&self) -> time::Duration{
+        self.0
+    }
+
+    /// Return the number of whole seconds
+    pub fn as_secs(&self) -> u64{
+        self.0.as_secs()
+    }
+
+    pub fn is_zero(&self) -> bool{
+        self.0 == time::Duration::from_secs(0)
+    }
+}
+
+
+impl From<time::Duration> for HumanDuration{
+    fn from(duration: time::Duration) -> Self{
+        HumanDuration(duration)
+    }
+}
+
+impl From<HumanDuration> for time::Duration{
+    fn from(duration: HumanDuration) -> Self{
+        duration.0
+    }
+}
+
+
+impl FromStr for HumanDuration{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>{
+        let trimmed = s.trim();
+        if trimmed.is_empty(){
+            return Err("expected a duration like 90s, 1h30m or 14d".to_string());
+        }
+        if let Ok(secs) = trimmed.parse::<u64>(){
+            return Ok(Self::from_secs(secs));
+        }
+
+        let mut millis: u64 = 0;
+        let mut rest = trimmed;
+        while !rest.is_empty(){
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            if digits == 0{
+                return Err(format!("\"{}\" is not a duration, expected a number at \"{}\"", trimmed, rest));
+            }
+            let number: u64 = rest[..digits].parse().map_err(|_| format!("\"{}\" is too long", trimmed))?;
+            rest = &rest[digits..];
+            let unit_len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+            let unit = &rest[..unit_len];
+            let factor = match DURATION_UNITS.iter().find(|(name, _)| *name == unit){
+                Some((_, factor)) => factor,
+                None if unit.is_empty() => return Err(format!("\"{}\" is missing a unit (w, d, h, m, s or ms)", trimmed)),
+                None => return Err(format!("\"{}\" has the unknown unit \"{}\" (expected w, d, h, m, s or ms)", trimmed, unit))
+            };
+            millis = number.checked_mul(*factor)
+                           .and_then(|m| millis.checked_add(m))
+                           .ok_or_else(|| format!("\"{}\" is too long", trimmed))?;
+            rest = rest[unit_len..].trim_start();
+        }
+        Ok(HumanDuration(time::Duration::from_millis(millis)))
+    }
+}
+
+
+/// Displays the duration with the largest fitting units, e.g. `1d12h`
+impl fmt::Display for HumanDuration{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let mut millis = self.0.as_secs()*1000 + u64::from(self.0.subsec_millis());
+        if millis == 0{
+            return write!(f, "0s");
+        }
+        // Skip weeks, "14d" is easier to read than "2w"
+        for (name, factor) in DURATION_UNITS.iter().skip(1){
+            if millis >= *factor{
+                write!(f, "{}{}", millis / factor, name)?;
+                millis %= factor;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+impl Serialize for HumanDuration{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer{
+        serializer.serialize_str(self.to_string().as_str())
+    }
+}
+
+
+struct HumanDurationVisitor;
+
+impl<'de> Visitor<'de> for HumanDurationVisitor{
+    type Value = HumanDuration;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "a duration like \"90s\", \"1h30m\" or \"14d\", or a number of seconds")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: de::Error{
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> where E: de::Error{
+        Ok(HumanDuration::from_secs(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> where E: de::Error{
+        if v < 0{
+            return Err(E::custom(format!("a duration can't be negative, got {}", v)));
+        }
+        Ok(HumanDuration::from_secs(v as u64))
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanDuration{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de>{
+        deserializer.deserialize_any(HumanDurationVisitor)
+    }
+}
+
+
+
+/// The units a ByteSize can be written in, with their size in bytes. Ordered
+/// from the largest to the smallest. Decimal units come before their binary
+/// counterpart, so sizes are displayed decimal when possible
+const BYTE_UNITS: [(&str, u64); 9] = [("TB",  1_000_000_000_000),
+                                      ("TiB", 1 << 40),
+                                      ("GB",  1_000_000_000),
+                                      ("GiB", 1 << 30),
+                                      ("MB",  1_000_000),
+                                      ("MiB", 1 << 20),
+                                      ("KB",  1_000),
+                                      ("KiB", 1 << 10),
+                                      ("B",   1)];
+
+/// The unit of plain numbers, as the sizes used to be integers in GB
+const LEGACY_BYTE_UNIT: u64 = 1_000_000_000;
+
+
+/// A regular expression matching everything `ByteSize` parses (used by the
+/// JSON schema)
+pub const SIZE_PATTERN: &str = r"^\s*(\d+(\.\d*)?|\.\d+)\s*([kKmMgGtT][iI]?[bB]|[bB])?\s*$";
+
+
+/// A size written like `"500MB"`, `"2GiB"` or `"1.5GB"`. KB, MB, GB and TB
+/// are powers of 1000, KiB, MiB, GiB and TiB powers of 1024. Plain numbers
+/// are read as GB. Displayed and serialized in the same notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ByteSize(u64);
+
+
+impl ByteSize{
+    pub fn from_bytes(bytes: u64) -> Self{
+        ByteSize(bytes)
+    }
+
+    pub fn from_gb(gb: u64) -> Self{
+        ByteSize(gb*LEGACY_BYTE_UNIT)
+    }
+
+    /// Return the size in bytes
+    pub fn as_bytes(&self) -> u64{
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool{
+        self.0 == 0
+    }
+}
+
+
+impl FromStr for ByteSize{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>{
+        let trimmed = s.trim();
+        let number_len = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(number_len);
+        if number.is_empty(){
+            return Err(format!("\"{}\" is not a size, expected something like 500MB or 2GiB", trimmed));
+        }
+        let unit = unit.trim();
+        let factor = if unit.is_empty(){
+            LEGACY_BYTE_UNIT
+        }else{
+            match BYTE_UNITS.iter().find(|(name, _)| name.eq_ignore_ascii_case(unit)){
+                Some((_, factor)) => *factor,
+                None => return Err(format!("\"{}\" has the unknown unit \"{}\" (expected B, KB, MB, GB, TB, KiB, MiB, GiB or TiB)", trimmed, unit))
+            }
+        };
+        let too_large = || format!("\"{}\" is too large", trimmed);
+        let bytes = match number.parse::<u64>(){
+            Ok(n) => n.checked_mul(factor).ok_or_else(too_large)?,
+            Err(_) => {
+                let n: f64 = number.parse().map_err(|_| format!("\"{}\" is not a number", number))?;
+                let bytes = (n * factor as f64).round();
+                if bytes >= u64::MAX as f64{
+                    return Err(too_large());
+                }
+                bytes as u64
+            }
+        };
+        Ok(ByteSize(bytes))
+    }
+}
+
+
+/// Displays the size with the largest unit it is a whole multiple of, e.g.
+/// `500MB`, `2GiB` or `1536B`
+impl fmt::Display for ByteSize{
+    // u64::is_multiple_of needs Rust 1.87
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let (name, factor) = BYTE_UNITS.iter()
+                                       .find(|(_, factor)| self.0 != 0 && self.0 % *factor == 0)
+                                       .unwrap_or(&("B", 1));
+        write!(f, "{}{}", self.0 / factor, name)
+    }
+}
+
+
+impl Serialize for ByteSize{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer{
+        serializer.serialize_str(self.to_string().as_str())
+    }
+}
+
+
+struct ByteSizeVisitor;
+
+impl<'de> Visitor<'de> for ByteSizeVisitor{
+    type Value = ByteSize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "a size like \"500MB\" or \"2GiB\", or a number of GB")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: de::Error{
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> where E: de::Error{
+        v.checked_mul(LEGACY_BYTE_UNIT)
+         .map(ByteSize)
+         .ok_or_else(|| E::custom(format!("{}GB is too large", v)))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> where E: de::Error{
+        if v < 0{
+            return Err(E::custom(format!("a size can't be negative, got {}", v)));
+        }
+        self.visit_u64(v as u64)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de>{
+        deserializer.deserialize_any(ByteSizeVisitor)
+    }
+}
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use units::*;
+    use toml;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!("14d".parse::<HumanDuration>().unwrap(), HumanDuration::from_mins(60*24*14));
+        assert_eq!("90s".parse::<HumanDuration>().unwrap(), HumanDuration::from_secs(90));
+        assert_eq!("1h30m".parse::<HumanDuration>().unwrap(), HumanDuration::from_mins(90));
+        assert_eq!("1h 30m".parse::<HumanDuration>().unwrap(), HumanDuration::from_mins(90));
+        assert_eq!("2w".parse::<HumanDuration>().unwrap(), HumanDuration::from_mins(60*24*14));
+        assert_eq!("60".parse::<HumanDuration>().unwrap(), HumanDuration::from_secs(60));
+        assert_eq!("1500ms".parse::<HumanDuration>().unwrap().as_std(), time::Duration::from_millis(1500));
+        assert!("".parse::<HumanDuration>().is_err());
+        assert!("10 minutes".parse::<HumanDuration>().is_err());
+        assert!("h".parse::<HumanDuration>().is_err());
+        assert!("-5s".parse::<HumanDuration>().is_err());
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        assert_eq!(HumanDuration::from_mins(60*24*14).to_string(), "14d");
+        assert_eq!(HumanDuration::from_secs(90).to_string(), "1m30s");
+        assert_eq!(HumanDuration::from_secs(0).to_string(), "0s");
+        assert_eq!(HumanDuration::from(time::Duration::from_millis(1500)).to_string(), "1s500ms");
+        for d in &["1d12h", "15m", "1h1m1s", "3s250ms"]{
+            assert_eq!(d.parse::<HumanDuration>().unwrap().to_string(), *d);
+        }
+    }
+
+    #[test]
+    fn deserialize_legacy_integers() {
+        #[derive(Deserialize)]
+        struct T{ a: HumanDuration, b: HumanDuration }
+        let t: T = toml::from_str("a = \"1h\"\nb = 30\n").unwrap();
+        assert_eq!(t.a, HumanDuration::from_mins(60));
+        assert_eq!(t.b, HumanDuration::from_secs(30));
+        assert!(toml::from_str::<T>("a = -1\nb = 1\n").is_err());
+    }
+
+    #[test]
+    fn byte_sizes() {
+        assert_eq!("500MB".parse::<ByteSize>().unwrap().as_bytes(), 500_000_000);
+        assert_eq!("2GiB".parse::<ByteSize>().unwrap().as_bytes(), 2*1024*1024*1024);
+        assert_eq!("1.5 GB".parse::<ByteSize>().unwrap().as_bytes(), 1_500_000_000);
+        assert_eq!("64kib".parse::<ByteSize>().unwrap().as_bytes(), 64*1024);
+        assert_eq!("2".parse::<ByteSize>().unwrap(), ByteSize::from_gb(2));
+        assert!("MB".parse::<ByteSize>().is_err());
+        assert!("2 parsecs".parse::<ByteSize>().is_err());
+
+        for s in &["500MB", "2GiB", "1536B", "0B", "3TB"]{
+            assert_eq!(s.parse::<ByteSize>().unwrap().to_string(), *s);
+        }
+        assert_eq!(ByteSize::from_gb(2).to_string(), "2GB");
+
+        #[derive(Deserialize)]
+        struct T{ a: ByteSize, b: ByteSize }
+        let t: T = toml::from_str("a = \"500MB\"\nb = 2\n").unwrap();
+        assert_eq!(t.a, ByteSize::from_bytes(500_000_000));
+        assert_eq!(t.b, ByteSize::from_gb(2));
+    }
+}
@@ -0,0 +1,203 @@
+//! Semantic checks of a Config. Deserialization only makes sure every value
+//! has the right type, the checks here make sure the values make sense
+//! together (e.g. a minimum is not bigger than its maximum). Every problem is
+//! reported as a Issue with the dotted key of the field it concerns.
+use ::*;
+use std::fmt;
+use url::Url;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity{
+    /// The value works, but is probably not what was intended
+    Warning,
+    /// The value would make a service fail or misbehave
+    Error
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue{
+    pub severity: Severity,
+    pub key: String,
+    pub message: String
+}
+
+impl Issue{
+    pub(crate) fn error<K, M>(key: K, message: M) -> Self where K: Into<String>, M: Into<String>{
+        Self{ severity: Severity::Error, key: key.into(), message: message.into() }
+    }
+
+    pub(crate) fn warning<K, M>(key: K, message: M) -> Self where K: Into<String>, M: Into<String>{
+        Self{ severity: Severity::Warning, key: key.into(), message: message.into() }
+    }
+
+    /// Returns true if this is a error (and not just a warning)
+    pub fn is_error(&self) -> bool{
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Issue{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let label = match self.severity{
+            Severity::Warning => "Warning",
+            Severity::Error   => "Error"
+        };
+        write!(f, "{} in {}: {}", label, self.key, self.message)
+    }
+}
+
+
+impl Config{
+    /// Check the values of the Config and return all issues found
+    pub fn validate(&self) -> Vec<Issue>{
+        let mut issues = Vec::new();
+        if self.servername.trim().is_empty(){
+            issues.push(Issue::warning("servername", "is empty, the frontend will show no name"));
+        }
+        self.paths.validate_into(&mut issues);
+        self.flaskbender.validate_into(&mut issues);
+        self.rabbitmq.validate_into(&mut issues);
+        self.janitor.validate_into(&mut issues);
+        self.worker.validate_into(&mut issues);
+        issues
+    }
+
+    /// Validate the Config, return the warnings if there are no errors and
+    /// a `ConfigError::Invalid` with all issues otherwise
+    pub fn ensure_valid(&self) -> ConfigResult<Vec<Issue>>{
+        let issues = self.validate();
+        if issues.iter().any(|i| i.is_error()){
+            Err(ConfigError::Invalid(issues))
+        }else{
+            Ok(issues)
+        }
+    }
+}
+
+
+impl Paths{
+    fn validate_into(&self, issues: &mut Vec<Issue>){
+        for (key, value) in &[("paths.config", &self.config),
+                              ("paths.private", &self.private),
+                              ("paths.upload", &self.upload)]{
+            if value.trim().is_empty(){
+                issues.push(Issue::error(*key, "is empty"));
+            }else if !PathBuf::from(value.as_str()).is_absolute(){
+                issues.push(Issue::warning(*key, format!("{} is a relative path, it depends on the working directory of each service", value)));
+            }
+        }
+        // The secrets may be written on another machine, so this is no error
+        for (key, result) in &[("paths.private_owner", permissions::resolve_user(self.private_owner.as_str())),
+                               ("paths.private_group", permissions::resolve_group(self.private_group.as_str()))]{
+            if let Err(err) = result{
+                issues.push(Issue::warning(*key, err.to_string()));
+            }
+        }
+    }
+}
+
+
+impl Flaskbender{
+    fn validate_into(&self, issues: &mut Vec<Issue>){
+        if self.upload_limit.is_zero(){
+            issues.push(Issue::warning("flaskbender.upload_limit", "is 0, no uploads will be accepted"));
+        }
+        match Url::parse(self.upload_url.as_str()){
+            Ok(ref url) if url.scheme() == "http" || url.scheme() == "https" => (),
+            Ok(url) => issues.push(Issue::error("flaskbender.upload_url", format!("expected a http or https URL, got scheme {}", url.scheme()))),
+            Err(err) => issues.push(Issue::error("flaskbender.upload_url", format!("{} is not a valid URL: {}", self.upload_url, err)))
+        }
+        if self.job_cookie_name.trim().is_empty(){
+            issues.push(Issue::error("flaskbender.job_cookie_name", "is empty"));
+        }
+    }
+}
+
+
+impl RabbitMQ{
+    fn validate_into(&self, issues: &mut Vec<Issue>){
+        match Url::parse(self.url.as_str()){
+            Ok(ref url) if url.scheme() != "amqp" && url.scheme() != "amqps" => {
+                issues.push(Issue::error("rabbitmq.url", format!("expected a amqp:// or amqps:// URL, got scheme {}", url.scheme())));
+            },
+            Ok(ref url) if url.host_str().unwrap_or("").is_empty() => {
+                issues.push(Issue::error("rabbitmq.url", format!("{} has no host", self.url)));
+            },
+            Ok(_) => (),
+            Err(err) => issues.push(Issue::error("rabbitmq.url", format!("{} is not a valid URL: {}", self.url, err)))
+        }
+    }
+}
+
+
+impl Janitor{
+    fn validate_into(&self, issues: &mut Vec<Issue>){
+        if self.checking_period.is_zero(){
+            issues.push(Issue::error("janitor.checking_period", "must be bigger than 0"));
+        }
+        let ranges = [("error_deletion", self.error_deletion_min, self.error_deletion_max),
+                      ("finish_deletion", self.finish_deletion_min, self.finish_deletion_max),
+                      ("cancel_deletion", self.cancel_deletion_min, self.cancel_deletion_max)];
+        for (name, min, max) in ranges.iter(){
+            if min > max{
+                issues.push(Issue::error(format!("janitor.{}_min", name),
+                                         format!("{} is longer than janitor.{}_max ({})", min, name, max)));
+            }
+        }
+    }
+}
+
+
+impl Worker{
+    fn validate_into(&self, issues: &mut Vec<Issue>){
+        if self.heart_rate.is_zero(){
+            issues.push(Issue::error("worker.heart_rate", "must be bigger than 0"));
+        }
+        if self.workload == 0{
+            issues.push(Issue::error("worker.workload", "must be bigger than 0, the worker wouldn't accept any frames"));
+        }
+        if self.disklimit.is_zero(){
+            issues.push(Issue::warning("worker.disklimit", "is 0, the worker might fill up the disk completely"));
+        }
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use validation::*;
+
+    #[test]
+    fn default_is_valid() {
+        let c = Config::default();
+        assert_eq!(c.validate(), Vec::new());
+        assert!(c.ensure_valid().is_ok());
+    }
+
+    #[test]
+    fn invalid_values() {
+        let mut c = Config::default();
+        c.janitor.error_deletion_min = "15d".parse().unwrap();
+        c.worker.heart_rate = HumanDuration::from_secs(0);
+        c.rabbitmq.url = "http://localhost/".to_string();
+        c.flaskbender.upload_url = "not a url".to_string();
+        c.worker.disklimit = ByteSize::from_bytes(0);
+        let issues = c.validate();
+        let errors: Vec<&str> = issues.iter().filter(|i| i.is_error()).map(|i| i.key.as_str()).collect();
+        assert_eq!(errors, vec!["flaskbender.upload_url",
+                                "rabbitmq.url",
+                                "janitor.error_deletion_min",
+                                "worker.heart_rate"]);
+        assert!(issues.iter().any(|i| i.severity == Severity::Warning && i.key == "worker.disklimit"));
+        match c.ensure_valid(){
+            Err(ConfigError::Invalid(i)) => assert_eq!(i, issues),
+            other => panic!("Expected a Invalid error, got {:?}", other)
+        }
+    }
+}
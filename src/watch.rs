@@ -0,0 +1,184 @@
+//! Hot reload for long running services. A ConfigWatcher monitors the files
+//! a ConfigBuilder reads (the config.toml, the drop-in directory and the host
+//! file), waits until writes have settled, rebuilds and validates the Config
+//! and reports the result to the subscriber. If the new Config is invalid the
+//! previous one stays in place.
+//!
+//! ```ignore
+//! let (watcher, events) = ConfigWatcher::new(ConfigBuilder::new(), Duration::from_secs(2))?;
+//! for event in events{
+//!     match event{
+//!         ReloadEvent::Reloaded(config, changes) => restart_what_changed(config, changes),
+//!         ReloadEvent::Rejected(err) => eprintln!("Keeping the old config: {}", err)
+//!     }
+//! }
+//! ```
+use ::*;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use notify::{watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+
+/// What happened after a watched file changed
+#[derive(Debug)]
+pub enum ReloadEvent{
+    /// The Config was rebuilt and differs from the previous one
+    Reloaded(Box<Config>, Vec<Change>),
+    /// The files changed, but the new Config couldn't be built or is invalid.
+    /// The previous Config is kept
+    Rejected(ConfigError)
+}
+
+
+pub struct ConfigWatcher{
+    current: Arc<Mutex<Config>>,
+    // Dropping the watcher stops the reload thread
+    _watcher: RecommendedWatcher
+}
+
+
+impl ConfigWatcher{
+    /// Build the initial Config and start watching. Events are delivered
+    /// through the returned Receiver
+    pub fn new(builder: ConfigBuilder, debounce: Duration) -> ConfigResult<(Self, Receiver<ReloadEvent>)>{
+        let (tx, rx) = channel();
+        let watcher = Self::start(builder, debounce, move |event| tx.send(event).is_ok())?;
+        Ok((watcher, rx))
+    }
+
+    /// Like `ConfigWatcher::new()` but calls `callback` for every event
+    pub fn with_callback<F>(builder: ConfigBuilder, debounce: Duration, mut callback: F) -> ConfigResult<Self>
+        where F: FnMut(ReloadEvent) + Send + 'static{
+        Self::start(builder, debounce, move |event| { callback(event); true })
+    }
+
+    /// Return a copy of the current Config
+    pub fn current(&self) -> Config{
+        self.current.lock().expect("The reload thread panicked").clone()
+    }
+
+    /// `deliver` returns false once nobody is interested in events anymore
+    fn start<F>(builder: ConfigBuilder, debounce: Duration, mut deliver: F) -> ConfigResult<Self>
+        where F: FnMut(ReloadEvent) -> bool + Send + 'static{
+        let initial = builder.build()?;
+        let current = Arc::new(Mutex::new(initial));
+
+        let (notify_tx, notify_rx): (Sender<DebouncedEvent>, Receiver<DebouncedEvent>) = channel();
+        let mut w = watcher(notify_tx, debounce).map_err(|err| ConfigError::Watch(err.to_string()))?;
+        let files = builder.watched_paths();
+        for dir in watched_dirs(&files){
+            w.watch(dir.as_str(), RecursiveMode::NonRecursive)
+             .map_err(|err| ConfigError::Watch(format!("{}: {}", dir, err)))?;
+        }
+
+        let shared = current.clone();
+        thread::spawn(move || {
+            // Ends when the watcher (and with it the sender) is dropped
+            for event in notify_rx{
+                if !is_relevant(&event, &files){
+                    continue;
+                }
+                let reload = match builder.build(){
+                    Ok(config) => {
+                        let mut current = shared.lock().expect("A subscriber panicked");
+                        let changes = current.diff(&config);
+                        if changes.is_empty(){
+                            continue;
+                        }
+                        *current = config.clone();
+                        ReloadEvent::Reloaded(Box::new(config), changes)
+                    },
+                    Err(err) => ReloadEvent::Rejected(err)
+                };
+                if !deliver(reload){
+                    break;
+                }
+            }
+        });
+
+        Ok(Self{ current, _watcher: w })
+    }
+}
+
+
+/// Return the existing directories that contain or are the watched paths
+fn watched_dirs(files: &[String]) -> Vec<String>{
+    let mut dirs: Vec<String> = files.iter()
+        .map(|f| {
+            let p = PathBuf::from(f.as_str());
+            if p.is_dir(){
+                p
+            }else{
+                let mut parent = p.clone();
+                parent.pop();
+                parent
+            }
+        })
+        .filter(|d| d.is_dir())
+        .map(|d| d.to_string_lossy().to_string())
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+
+/// Returns true if the event concerns one of the watched files or a file
+/// within a watched directory
+fn is_relevant(event: &DebouncedEvent, files: &[String]) -> bool{
+    let concerns = |p: &PathBuf| files.iter().any(|f| {
+        let f = PathBuf::from(f.as_str());
+        *p == f || (f.is_dir() && p.parent() == Some(f.as_path()))
+    });
+    match event{
+        DebouncedEvent::Create(p) | DebouncedEvent::Write(p) |
+        DebouncedEvent::Chmod(p) | DebouncedEvent::Remove(p) => concerns(p),
+        DebouncedEvent::Rename(from, to) => concerns(from) || concerns(to),
+        DebouncedEvent::Rescan => true,
+        _ => false
+    }
+}
+
+
+
+
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use watch::*;
+
+    #[test]
+    fn reload_and_reject() {
+        let mut dir = std::env::temp_dir();
+        dir.push("bender_config_watch");
+        let dir = dir.to_string_lossy().to_string();
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(dir.as_str()).unwrap();
+        let path = dir.push("config.toml");
+        fs::write(path.as_str(), "[worker]\nworkload = 1\n").unwrap();
+
+        let builder = ConfigBuilder::empty().system_file(path.as_str());
+        let (watcher, events) = ConfigWatcher::new(builder, Duration::from_millis(100)).unwrap();
+
+        fs::write(path.as_str(), "[worker]\nworkload = 4\n").unwrap();
+        match events.recv_timeout(Duration::from_secs(10)){
+            Ok(ReloadEvent::Reloaded(config, changes)) => {
+                assert_eq!(config.worker.workload, 4);
+                assert_eq!(changes.len(), 1);
+                assert_eq!(changes[0].key, "worker.workload");
+            },
+            other => panic!("Expected a reload, got {:?}", other)
+        }
+
+        fs::write(path.as_str(), "[worker]\nworkload = 0\n").unwrap();
+        match events.recv_timeout(Duration::from_secs(10)){
+            Ok(ReloadEvent::Rejected(ConfigError::Invalid(_))) => (),
+            other => panic!("Expected a rejected reload, got {:?}", other)
+        }
+        assert_eq!(watcher.current().worker.workload, 4);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -2,67 +2,225 @@
 //! There are two main cases:
 //! A) A new configuration should be generated
 //! B) A existing configuration should be updated
+//!
+//! The dialogs don't talk to the terminal directly, they go through a
+//! `Prompter`. The `TerminalPrompter` asks the user, the `ScriptedPrompter`
+//! plays back prepared answers (for tests) and the `AnswersPrompter` of the
+//! `answers` module answers from a file or the environment.
 use ::*;
 use std::str;
 use std::fmt::{Display, Debug};
+use std::collections::VecDeque;
 use console::Term;
 use colored::*;
 
 
 pub trait Dialog {
-    fn ask() -> Self;
-    fn compare(&self, other: Option<&Self>) -> Self;
+    fn ask(prompter: &mut dyn Prompter) -> Self;
+    fn compare(&self, prompter: &mut dyn Prompter, other: Option<&Self>) -> Self;
+    /// The explanations of the fields by field name, the explanation of the
+    /// dialog itself has the empty name
+    fn descriptions() -> Vec<(&'static str, &'static str)>{
+        Vec::new()
+    }
+}
+
+
+/// The backend the dialogs use to ask questions and print information
+pub trait Prompter{
+    /// Ask for the value of the dotted `key`. Answers for which `validate`
+    /// returns a error are not accepted
+    fn input(&mut self, key: &str, prompt: &str, default: &str, validate: &dyn Fn(&str) -> Result<(), String>) -> String;
+    /// Let the user choose one of the items, return its index
+    fn select(&mut self, prompt: &str, items: &[String], default: usize) -> usize;
+    /// Ask a yes/no question
+    fn confirm(&mut self, prompt: &str, default: bool) -> bool;
+    /// Print a section label like ---------------- foo ----------------
+    fn print_sectionlabel(&mut self, message: &str);
+    /// Print a highlighted block
+    fn print_block(&mut self, message: &str);
+    /// Print a line of explanation
+    fn print_text(&mut self, message: &str);
+    /// Show two differing values besides each other
+    fn print_comparison(&mut self, this: &str, that: &str){
+        self.print_text(format!("{} != {}", this, that).as_str());
+    }
 }
 
+
+/// Ask for the value of the dotted `key` and parse it into T
+pub fn input<T>(prompter: &mut dyn Prompter, key: &str, prompt: &str, default: T) -> T
+    where T: Display + std::str::FromStr,
+    <T as std::str::FromStr>::Err: Display{
+    let validate = |raw: &str| raw.trim().parse::<T>().map(|_| ()).map_err(|err| err.to_string());
+    let raw = prompter.input(key, prompt, default.to_string().as_str(), &validate);
+    raw.trim().parse().unwrap_or(default)
+}
+
+
 /// Compare two items of same type to each other and display them besides each\
 /// other. Display a selector
-pub fn differ<T>(this: T, opt_that: Option<T>) -> T 
-    where T: 'static + PartialEq + Display + Clone + Debug + std::str::FromStr,
-    <T as std::str::FromStr>::Err: Display + Debug{
+pub fn differ<T>(prompter: &mut dyn Prompter, this: T, opt_that: Option<T>) -> T
+    where T: PartialEq + Display + std::str::FromStr,
+    <T as std::str::FromStr>::Err: Display{
     match opt_that{
         Some(that) => {
             if this != that{
-                compareprint(&this, &that, width(), "!=");
-                let choice = Select::new()
-                                .item(format!("{}", this).as_str())
-                                .item(format!("{}", that).as_str())
-                                .item("Manual override")
-                                .default(0)
-                                .interact()
-                                .expect("Couldn't display dialog.");
-                match choice{
-                    2 => {
-                        let x: T = Input::<T>::new().default(this).interact().expect("Couldn't display dialog.");
-                        x
-                    }
+                prompter.print_comparison(this.to_string().as_str(), that.to_string().as_str());
+                let items = vec![this.to_string(), that.to_string(), "Manual override".to_string()];
+                match prompter.select("", &items, 0){
+                    2 => input(prompter, "", "", this),
                     1 => that,
                     _ => this
                 }
             }else{
                 this
-            } 
+            }
         },
         None      => {
-            println!("{}{}", " Existing value -> ".black().on_yellow(), format!(" {} ", &this).black().on_green());
-            let choice = Select::new()
-                            .item("Keep")
-                            .item("Manual override")
-                            .default(0)
-                            .interact()
-                            .expect("Couldn't display dialog.");
-            match choice{
-                1 => {
-                    let x: T = Input::<T>::new().default(this).interact().expect("Couldn't display dialog.");
-                    x
-                }
+            prompter.print_text(format!("{}{}", " Existing value -> ".black().on_yellow(), format!(" {} ", &this).black().on_green()).as_str());
+            let items = vec!["Keep".to_string(), "Manual override".to_string()];
+            match prompter.select("", &items, 0){
+                1 => input(prompter, "", "", this),
                 _ => this
             }
         }
     }
 }
 
+
+/// Asks the user on the terminal
+#[derive(Debug, Default)]
+pub struct TerminalPrompter;
+
+impl TerminalPrompter{
+    pub fn new() -> Self{
+        TerminalPrompter
+    }
+}
+
+impl Prompter for TerminalPrompter{
+    fn input(&mut self, _key: &str, prompt: &str, default: &str, validate: &dyn Fn(&str) -> Result<(), String>) -> String{
+        loop{
+            let raw = Input::<String>::new().with_prompt(prompt)
+                                            .default(default.to_string())
+                                            .interact()
+                                            .expect("Couldn't display dialog.");
+            match validate(raw.as_str()){
+                Ok(_) => return raw,
+                Err(err) => errorprint(err)
+            }
+        }
+    }
+
+    fn select(&mut self, prompt: &str, items: &[String], default: usize) -> usize{
+        if !prompt.is_empty(){
+            println!("{}", prompt);
+        }
+        let mut select = Select::new();
+        for item in items{
+            select.item(item.as_str());
+        }
+        select.default(default).interact().expect("Couldn't display dialog.")
+    }
+
+    fn confirm(&mut self, prompt: &str, default: bool) -> bool{
+        let items = vec!["Yes".to_string(), "No".to_string()];
+        self.select(prompt, &items, if default { 0 }else{ 1 }) == 0
+    }
+
+    fn print_sectionlabel(&mut self, message: &str){
+        print_sectionlabel(message);
+    }
+
+    fn print_block(&mut self, message: &str){
+        print_block(message);
+    }
+
+    fn print_text(&mut self, message: &str){
+        println!("{}", message);
+    }
+
+    fn print_comparison(&mut self, this: &str, that: &str){
+        compareprint(this, that, width(), "!=");
+    }
+}
+
+
+/// Plays back prepared answers, one per `input`, `select` or `confirm`, and
+/// records everything that would have been shown. A empty answer (or running
+/// out of answers) accepts the default. Meant for testing dialogs:
+/// ```ignore
+/// let mut prompter = ScriptedPrompter::new(vec!["", "4"]);
+/// let worker = Worker::ask(&mut prompter);
+/// ```
+#[derive(Debug, Default)]
+pub struct ScriptedPrompter{
+    answers: VecDeque<String>,
+    /// Everything that was printed or asked, in order
+    pub output: Vec<String>
+}
+
+impl ScriptedPrompter{
+    pub fn new<I, S>(answers: I) -> Self where I: IntoIterator<Item=S>, S: Into<String>{
+        Self{
+            answers: answers.into_iter().map(|a| a.into()).collect(),
+            output: Vec::new()
+        }
+    }
+
+    /// The number of answers that haven't been used yet
+    pub fn remaining(&self) -> usize{
+        self.answers.len()
+    }
+
+    fn next(&mut self) -> Option<String>{
+        self.answers.pop_front().filter(|a| !a.is_empty())
+    }
+}
+
+impl Prompter for ScriptedPrompter{
+    /// Panics if the answer doesn't validate, as the script would otherwise
+    /// loop forever
+    fn input(&mut self, _key: &str, prompt: &str, default: &str, validate: &dyn Fn(&str) -> Result<(), String>) -> String{
+        let answer = self.next().unwrap_or_else(|| default.to_string());
+        if let Err(err) = validate(answer.as_str()){
+            panic!("Scripted answer \"{}\" to \"{}\" is invalid: {}", answer, prompt, err);
+        }
+        self.output.push(format!("{} [{}]: {}", prompt, default, answer));
+        answer
+    }
+
+    fn select(&mut self, prompt: &str, items: &[String], default: usize) -> usize{
+        let choice = self.next().map(|a| a.parse().expect("Scripted select answers must be a index"))
+                                .unwrap_or(default);
+        assert!(choice < items.len(), "Scripted select answer {} out of range", choice);
+        self.output.push(format!("{} [{}]: {}", prompt, items.join("|"), items[choice]));
+        choice
+    }
+
+    fn confirm(&mut self, prompt: &str, default: bool) -> bool{
+        let answer = self.next().map(|a| a == "y" || a == "yes" || a == "true").unwrap_or(default);
+        self.output.push(format!("{} [y/n]: {}", prompt, answer));
+        answer
+    }
+
+    fn print_sectionlabel(&mut self, message: &str){
+        self.output.push(format!("--- {} ---", message));
+    }
+
+    fn print_block(&mut self, message: &str){
+        self.output.push(message.to_string());
+    }
+
+    fn print_text(&mut self, message: &str){
+        self.output.push(message.to_string());
+    }
+}
+
+
 /// Print two elements for comparison
-fn compareprint<T, S>(left: T, right: T, w: usize, sign: S) 
+fn compareprint<T, S>(left: T, right: T, w: usize, sign: S)
 where T: Display, S: Into<String>{
     let sign = sign.into();
     let left = format!("{}", left);
@@ -140,3 +298,88 @@ pub fn print_block<S>(message: S) where S: Into<String>{
 
 
 
+// =============================== UNIT TESTS ================================
+
+#[cfg(test)]
+mod unit_tests {
+    use wizard::*;
+
+    #[test]
+    fn ask_scripted() {
+        let mut prompter = ScriptedPrompter::new(vec!["", "", "3", "20s"]);
+        let worker = Worker::ask(&mut prompter);
+        assert_eq!(prompter.remaining(), 0);
+        assert_eq!(worker.disklimit, ByteSize::from_gb(2));
+        assert_eq!(worker.workload, 3);
+        assert_eq!(worker.heart_rate, HumanDuration::from_secs(20));
+        assert!(prompter.output.contains(&"--- bender-worker ---".to_string()));
+    }
+
+    #[test]
+    fn compare_scripted() {
+        let this = RabbitMQ{ url: "amqp://a//".to_string() };
+        let that = RabbitMQ{ url: "amqp://b//".to_string() };
+        // Take the other value
+        let mut prompter = ScriptedPrompter::new(vec!["1"]);
+        assert_eq!(this.compare(&mut prompter, Some(&that)).url, "amqp://b//");
+        // Keep the existing value
+        let mut prompter = ScriptedPrompter::new(Vec::<String>::new());
+        assert_eq!(this.compare(&mut prompter, None).url, "amqp://a//");
+        // Manual override
+        let mut prompter = ScriptedPrompter::new(vec!["1", "amqp://c//"]);
+        assert_eq!(this.compare(&mut prompter, None).url, "amqp://c//");
+    }
+
+    #[test]
+    fn compare_keeps_skipped() {
+        let paths = Paths{ config: "/srv/bender/config.toml".to_string(),
+                           private_owner: "www-data".to_string(),
+                           private_group: "bender".to_string(),
+                           ..Paths::default() };
+        let mut prompter = ScriptedPrompter::new(Vec::<String>::new());
+        assert_eq!(paths.compare(&mut prompter, Some(&Paths::default())), paths);
+        let flaskbender = Flaskbender{ upload_url: "https://render.example/upload".to_string(), ..Flaskbender::default() };
+        let mut prompter = ScriptedPrompter::new(Vec::<String>::new());
+        assert_eq!(flaskbender.compare(&mut prompter, None), flaskbender);
+    }
+
+    #[test]
+    fn derived_texts() {
+        let mut prompter = ScriptedPrompter::new(Vec::<String>::new());
+        let janitor = Janitor::ask(&mut prompter);
+        assert_eq!(janitor, Janitor::default());
+        assert_eq!(prompter.output[1], "--- bender-janitor ---");
+        assert!(prompter.output[2].starts_with("The bender-janitor service cleans up"));
+        assert!(prompter.output[3].starts_with("How frequenctly should the janitor check"));
+        assert!(prompter.output[4].starts_with("\nThe bender-janitor will dynamically decide"));
+
+        let mut prompter = ScriptedPrompter::new(Vec::<String>::new());
+        janitor.compare(&mut prompter, Some(&janitor));
+        assert_eq!(prompter.output[2], "\n How often should the janitor check for cleanup? ");
+    }
+
+    #[derive(Debug, Default, PartialEq, Dialog)]
+    #[dialog(key = "farm")]
+    struct Farm{
+        #[dialog(prompt = "How many nodes?", default = "4")]
+        nodes: usize,
+        #[dialog(prompt = "How many cores per node?")]
+        cores: usize,
+        #[dialog(skip, default = "bender")]
+        name: String
+    }
+
+    #[test]
+    fn attribute_defaults() {
+        let mut prompter = ScriptedPrompter::new(Vec::<String>::new());
+        let farm = Farm::ask(&mut prompter);
+        assert_eq!(farm, Farm{ nodes: 4, cores: 0, name: "bender".to_string() });
+    }
+
+    #[test]
+    #[should_panic(expected = "is invalid")]
+    fn invalid_scripted_answer() {
+        let mut prompter = ScriptedPrompter::new(vec!["many"]);
+        input::<usize>(&mut prompter, "worker.workload", "How many?", 1);
+    }
+}